@@ -0,0 +1,57 @@
+use worm::core::{
+    ActiveFlagModel,
+    PrimaryKeyModel,
+};
+pub trait ActiveModels {
+    fn active_columns() -> Vec<(&'static str, &'static str, &'static str)>;
+}
+impl<A> ActiveModels for (A,)
+where
+    A: PrimaryKeyModel + ActiveFlagModel,
+{
+    fn active_columns() -> Vec<(&'static str, &'static str, &'static str)> {
+        return vec![ (A::DB, A::TABLE, A::ACTIVE) ];
+    }
+}
+impl<A, B> ActiveModels for (A, B)
+where
+    A: PrimaryKeyModel + ActiveFlagModel,
+    B: PrimaryKeyModel + ActiveFlagModel,
+{
+    fn active_columns() -> Vec<(&'static str, &'static str, &'static str)> {
+        return vec![
+            (A::DB, A::TABLE, A::ACTIVE),
+            (B::DB, B::TABLE, B::ACTIVE),
+        ];
+    }
+}
+impl<A, B, C> ActiveModels for (A, B, C)
+where
+    A: PrimaryKeyModel + ActiveFlagModel,
+    B: PrimaryKeyModel + ActiveFlagModel,
+    C: PrimaryKeyModel + ActiveFlagModel,
+{
+    fn active_columns() -> Vec<(&'static str, &'static str, &'static str)> {
+        return vec![
+            (A::DB, A::TABLE, A::ACTIVE),
+            (B::DB, B::TABLE, B::ACTIVE),
+            (C::DB, C::TABLE, C::ACTIVE),
+        ];
+    }
+}
+impl<A, B, C, D> ActiveModels for (A, B, C, D)
+where
+    A: PrimaryKeyModel + ActiveFlagModel,
+    B: PrimaryKeyModel + ActiveFlagModel,
+    C: PrimaryKeyModel + ActiveFlagModel,
+    D: PrimaryKeyModel + ActiveFlagModel,
+{
+    fn active_columns() -> Vec<(&'static str, &'static str, &'static str)> {
+        return vec![
+            (A::DB, A::TABLE, A::ACTIVE),
+            (B::DB, B::TABLE, B::ACTIVE),
+            (C::DB, C::TABLE, C::ACTIVE),
+            (D::DB, D::TABLE, D::ACTIVE),
+        ];
+    }
+}
@@ -0,0 +1,199 @@
+use {
+    crate::{
+        error::{
+            BuildliteError,
+            BuildliteErrorMatch,
+        },
+        guard::UpdateGuard,
+        ident,
+        interrupt::Interrupt,
+        observe::Observer,
+        param::{
+            inline_params,
+            Param,
+        },
+        plan,
+        query::QueryType,
+        retry::RetryPolicy,
+        transaction::in_savepoint,
+    },
+    std::sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    worm::core::{
+        DbCtx,
+        PrimaryKeyModel,
+    },
+};
+pub struct CompiledQuery<'query, T> {
+    query_type: QueryType,
+    sql: String,
+    params: Vec<Param<'query>>,
+    slots: Vec<Vec<usize>>,
+    sensitive: Vec<bool>,
+    retry: RetryPolicy,
+    interrupt: Interrupt,
+    observer: Observer,
+    guard: UpdateGuard,
+    columns: Vec<(String, String, String)>,
+    tables: Vec<(String, String, String)>,
+    scan_guard: Option<usize>,
+    validated: AtomicBool,
+    _value: Option<T>,
+}
+impl<'query, T> Clone for CompiledQuery<'query, T> {
+    fn clone(&self) -> Self {
+        return CompiledQuery {
+            query_type: self.query_type,
+            sql: self.sql.clone(),
+            params: self.params.clone(),
+            slots: self.slots.clone(),
+            sensitive: self.sensitive.clone(),
+            retry: self.retry,
+            interrupt: self.interrupt.clone(),
+            observer: self.observer.clone(),
+            guard: self.guard,
+            columns: self.columns.clone(),
+            tables: self.tables.clone(),
+            scan_guard: self.scan_guard,
+            validated: AtomicBool::new(self.validated.load(Ordering::Relaxed)),
+            _value: None,
+        };
+    }
+}
+impl<'query, T> CompiledQuery<'query, T> where T: PrimaryKeyModel {
+    pub(crate) fn new(
+        query_type: QueryType,
+        sql: String,
+        params: Vec<Param<'query>>,
+        slots: Vec<Vec<usize>>,
+        sensitive: Vec<bool>,
+        retry: RetryPolicy,
+        interrupt: Interrupt,
+        observer: Observer,
+        guard: UpdateGuard,
+        columns: Vec<(String, String, String)>,
+        tables: Vec<(String, String, String)>,
+        scan_guard: Option<usize>
+    ) -> Self {
+        return CompiledQuery {
+            query_type,
+            sql,
+            params,
+            slots,
+            sensitive,
+            retry,
+            interrupt,
+            observer,
+            guard,
+            columns,
+            tables,
+            scan_guard,
+            validated: AtomicBool::new(false),
+            _value: None,
+        };
+    }
+    pub fn sql(&self) -> &str {
+        return &self.sql;
+    }
+    pub fn to_debug_sql(&self) -> String {
+        let params = self.params.iter().collect::<Vec<&Param>>();
+        let literals = self.observer.render_params(&params, &self.sensitive);
+        return inline_params(&self.sql, literals);
+    }
+    pub fn param_count(&self) -> usize {
+        return self.slots.len();
+    }
+    pub fn bind(&mut self, slot: usize, value: impl Into<Param<'query>>) -> &mut Self {
+        if slot >= self.slots.len() {
+            panic!("Cannot bind parameter {} of a query with {} parameters", slot, self.slots.len());
+        }
+        let value = value.into();
+        for position in &self.slots[slot] {
+            self.params[*position] = value.clone();
+        }
+        return self;
+    }
+    fn preflight(&self, db: &mut impl DbCtx, params: &[&Param]) -> Result<(), BuildliteError> {
+        // columns only need checking once, later schema changes still fail to prepare
+        if !self.validated.load(Ordering::Relaxed) {
+            ident::validate_columns(db, &self.columns)?;
+            self.validated.store(true, Ordering::Relaxed);
+        }
+        return match self.scan_guard {
+            Some(max_rows) => {
+                let query_plan = plan::explain(db, &self.sql, params)?;
+                plan::check_scans(db, &query_plan, &self.tables, max_rows)
+            },
+            None => Ok(()),
+        };
+    }
+    pub fn execute_update(&self, db: &mut impl DbCtx) -> Result<usize, BuildliteError> {
+        match self.query_type {
+            QueryType::Select => {
+                panic!("Cannot execute an update from a select query");
+            },
+            QueryType::Update => {},
+        }
+        let params = self.params.iter().collect::<Vec<&Param>>();
+        return self.guard.check_filtered()
+            .and_then(|_| match self.guard.expect_rows {
+                Some(_) => in_savepoint(db, "buildlite_expect", |tx| {
+                    let affected = self.run_update(tx, &params)?;
+                    return self.guard.check_affected(affected);
+                }),
+                None => self.run_update(db, &params),
+            })
+            .map_err(|e| {
+                return e.with_query(&self.sql, self.observer.render_params(&params, &self.sensitive));
+            });
+    }
+    fn run_update(&self, db: &mut impl DbCtx, params: &[&Param]) -> Result<usize, BuildliteError> {
+        let count = |n: &usize| Some(*n);
+        return self.observer.run(&self.sql, params, &self.sensitive, count, || {
+            self.preflight(db, &params)?;
+            return self.interrupt.run(db, |db, watch| self.retry.run_watched(watch, || {
+                let param = worm::core::sql::params_from_iter(self.params.iter());
+                let c = db.use_connection();
+                let mut stmt = c.prepare_cached(&self.sql).quick_match()?;
+                return Ok(stmt.execute(param).quick_match()?);
+            }));
+        });
+    }
+    pub fn execute(&self, db: &mut impl DbCtx) -> Result<Vec<T>, BuildliteError> {
+        match self.query_type {
+            QueryType::Select => {},
+            QueryType::Update => {
+                panic!("Cannot execute a select on an update query");
+            },
+        }
+        let params = self.params.iter().collect::<Vec<&Param>>();
+        let count = |objs: &Vec<T>| Some(objs.len());
+        return self.observer.run(&self.sql, &params, &self.sensitive, count, || {
+            self.preflight(db, &params)?;
+            return self.interrupt.run(db, |db, watch| self.retry.run_watched(watch, || {
+                let param = worm::core::sql::params_from_iter(self.params.iter());
+                let c = db.use_connection();
+                let mut objs = Vec::new();
+                let mut stmt = c.prepare_cached(&self.sql).quick_match()?;
+                let mut rows = stmt.query(param).quick_match()?;
+                while let Some(row) = rows.next().quick_match()? {
+                    objs.push(T::from_row(row).quick_match()?);
+                }
+                return Ok(objs);
+            }));
+        }).map_err(|e| {
+            return e.with_query(&self.sql, self.observer.render_params(&params, &self.sensitive));
+        });
+    }
+    pub fn execute_row(&self, db: &mut impl DbCtx) -> Result<T, BuildliteError> {
+        let res = self.execute(db)?;
+        if res.len() == 0 {
+            return Err(BuildliteError::NoRowsError);
+        } else {
+            let val = res.into_iter().nth(0).unwrap();
+            return Ok(val);
+        }
+    }
+}
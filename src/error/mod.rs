@@ -1,19 +1,204 @@
 use {
     std::{
         error::Error as StdError,
+        fmt::Debug as StdDebug,
         fmt::Display as StdDisplay,
         fmt::Formatter as StdFormatter,
         fmt::Result as FmtResult,
+        time::Duration,
+    },
+    worm::core::sql::{
+        ffi,
+        Error as RusqliteError,
+        ErrorCode,
     },
-    worm::core::sql::Error as RusqliteError,
 };
 pub trait BuildliteErrorMatch<T, U>: Sized where U: StdError {
     fn quick_match(self) -> Result<T, BuildliteError>;
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintKind {
+    PrimaryKey,
+    NotNull,
+    Check,
+    Other,
+}
+#[derive(Clone)]
+pub struct QueryContext {
+    sql: String,
+    params: Vec<String>,
+}
+impl StdDebug for QueryContext {
+    fn fmt(&self, f: &mut StdFormatter<'_>) -> FmtResult {
+        return f.debug_struct("QueryContext")
+            .field("sql", &self.sql)
+            .field("param_count", &self.params.len())
+            .finish();
+    }
+}
+impl QueryContext {
+    pub fn sql(&self) -> &str {
+        return &self.sql;
+    }
+    pub fn param_count(&self) -> usize {
+        return self.params.len();
+    }
+    pub fn debug_params(&self) -> &[String] {
+        return &self.params;
+    }
+    fn suffix(query: &Option<Box<QueryContext>>) -> String {
+        return match query {
+            Some(query) => {
+                format!(" (query: \"{}\" with {} parameters)", query.sql, query.params.len())
+            },
+            None => String::new(),
+        };
+    }
+}
 #[derive(Debug)]
 pub enum BuildliteError {
     NoRowsError,
-    SQLError(RusqliteError),
+    TooManyRowsError,
+    BusyError(RusqliteError, Option<Box<QueryContext>>),
+    UniqueError {
+        columns: Vec<String>,
+        error: RusqliteError,
+        query: Option<Box<QueryContext>>,
+    },
+    ForeignKeyError(RusqliteError, Option<Box<QueryContext>>),
+    ConstraintError {
+        kind: ConstraintKind,
+        error: RusqliteError,
+        query: Option<Box<QueryContext>>,
+    },
+    TypeMismatchError(RusqliteError, Option<Box<QueryContext>>),
+    TimeoutError(Duration),
+    CancelledError,
+    FullScanError {
+        table: String,
+        detail: String,
+    },
+    UnfilteredUpdateError,
+    UnknownColumnError {
+        table: String,
+        column: String,
+    },
+    UnknownAliasError {
+        alias: String,
+    },
+    UnexpectedRowsError {
+        expected: usize,
+        actual: usize,
+    },
+    NoJoinPathError {
+        from: String,
+        to: String,
+    },
+    AmbiguousJoinError {
+        from: String,
+        to: String,
+        paths: usize,
+    },
+    SQLError(RusqliteError, Option<Box<QueryContext>>),
+}
+impl BuildliteError {
+    pub(crate) fn with_query<'a>(mut self, sql: &'a str, params: Vec<String>) -> Self {
+        let slot = match &mut self {
+            BuildliteError::BusyError(_, query) => query,
+            BuildliteError::UniqueError { query, .. } => query,
+            BuildliteError::ForeignKeyError(_, query) => query,
+            BuildliteError::ConstraintError { query, .. } => query,
+            BuildliteError::TypeMismatchError(_, query) => query,
+            BuildliteError::SQLError(_, query) => query,
+            _ => return self,
+        };
+        if slot.is_none() {
+            *slot = Some(Box::new(QueryContext {
+                sql: sql.to_string(),
+                params,
+            }));
+        }
+        return self;
+    }
+    pub fn query(&self) -> Option<&QueryContext> {
+        let query = match self {
+            BuildliteError::BusyError(_, query) => query,
+            BuildliteError::UniqueError { query, .. } => query,
+            BuildliteError::ForeignKeyError(_, query) => query,
+            BuildliteError::ConstraintError { query, .. } => query,
+            BuildliteError::TypeMismatchError(_, query) => query,
+            BuildliteError::SQLError(_, query) => query,
+            _ => return None,
+        };
+        return query.as_deref();
+    }
+    pub fn sql(&self) -> Option<&str> {
+        return self.query().map(|query| query.sql());
+    }
+    pub fn debug_params(&self) -> Option<&[String]> {
+        return self.query().map(|query| query.debug_params());
+    }
+    pub fn param_count(&self) -> Option<usize> {
+        return self.query().map(|query| query.param_count());
+    }
+    fn unique_columns<'a>(msg: &'a str) -> Vec<String> {
+        return match msg.split_once(": ") {
+            Some((_, columns)) => columns.split(", ")
+                .map(|column| column.to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+}
+impl From<RusqliteError> for BuildliteError {
+    fn from(e: RusqliteError) -> Self {
+        let (code, extended_code, msg) = match &e {
+            RusqliteError::SqliteFailure(err, msg) => {
+                (err.code, err.extended_code, msg.clone().unwrap_or_default())
+            },
+            RusqliteError::InvalidColumnType(..) |
+            RusqliteError::FromSqlConversionFailure(..) |
+            RusqliteError::IntegralValueOutOfRange(..) => {
+                return BuildliteError::TypeMismatchError(e, None);
+            },
+            _ => return BuildliteError::SQLError(e, None),
+        };
+        return match code {
+            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => {
+                BuildliteError::BusyError(e, None)
+            },
+            ErrorCode::ConstraintViolation => match extended_code {
+                ffi::SQLITE_CONSTRAINT_UNIQUE => BuildliteError::UniqueError {
+                    columns: Self::unique_columns(&msg),
+                    error: e,
+                    query: None,
+                },
+                ffi::SQLITE_CONSTRAINT_FOREIGNKEY => BuildliteError::ForeignKeyError(e, None),
+                ffi::SQLITE_CONSTRAINT_PRIMARYKEY => BuildliteError::ConstraintError {
+                    kind: ConstraintKind::PrimaryKey,
+                    error: e,
+                    query: None,
+                },
+                ffi::SQLITE_CONSTRAINT_NOTNULL => BuildliteError::ConstraintError {
+                    kind: ConstraintKind::NotNull,
+                    error: e,
+                    query: None,
+                },
+                ffi::SQLITE_CONSTRAINT_CHECK => BuildliteError::ConstraintError {
+                    kind: ConstraintKind::Check,
+                    error: e,
+                    query: None,
+                },
+                _ => BuildliteError::ConstraintError {
+                    kind: ConstraintKind::Other,
+                    error: e,
+                    query: None,
+                },
+            },
+            ErrorCode::TypeMismatch => BuildliteError::TypeMismatchError(e, None),
+            _ => BuildliteError::SQLError(e, None),
+        };
+    }
 }
 impl StdDisplay for BuildliteError {
     fn fmt(&self, f: &mut StdFormatter) -> FmtResult {
@@ -21,19 +206,86 @@ impl StdDisplay for BuildliteError {
             BuildliteError::NoRowsError => {
                 write!(f, "No rows found!")
             },
-            BuildliteError::SQLError(e) => {
-                let msg = &format!("{}", e);
+            BuildliteError::TooManyRowsError => {
+                write!(f, "Too many rows found!")
+            },
+            BuildliteError::BusyError(e, query) => {
+                write!(f, "Database is busy: {}{}", e, QueryContext::suffix(query))
+            },
+            BuildliteError::UniqueError { columns, query, .. } => {
+                write!(f, "Unique constraint violated on {}{}", columns.join(", "), QueryContext::suffix(query))
+            },
+            BuildliteError::ForeignKeyError(e, query) => {
+                write!(f, "Foreign key constraint violated: {}{}", e, QueryContext::suffix(query))
+            },
+            BuildliteError::ConstraintError { kind, error, query } => {
+                write!(f, "{:?} constraint violated: {}{}", kind, error, QueryContext::suffix(query))
+            },
+            BuildliteError::TypeMismatchError(e, query) => {
+                write!(f, "Type mismatch: {}{}", e, QueryContext::suffix(query))
+            },
+            BuildliteError::TimeoutError(timeout) => {
+                write!(f, "Query timed out after {:?}", timeout)
+            },
+            BuildliteError::CancelledError => {
+                write!(f, "Query was cancelled")
+            },
+            BuildliteError::FullScanError { table, detail } => {
+                write!(f, "Query plan scans {} without an index: {}", table, detail)
+            },
+            BuildliteError::UnfilteredUpdateError => {
+                write!(f, "Refusing to update every row without all_rows()")
+            },
+            BuildliteError::UnknownColumnError { table, column } => {
+                write!(f, "Unknown column {:?} on {}", column, table)
+            },
+            BuildliteError::UnknownAliasError { alias } => {
+                write!(f, "Unknown table alias {:?}", alias)
+            },
+            BuildliteError::UnexpectedRowsError { expected, actual } => {
+                write!(f, "Expected {} affected rows but found {}", expected, actual)
+            },
+            BuildliteError::NoJoinPathError { from, to } => {
+                write!(f, "No join path from {} to {}", from, to)
+            },
+            BuildliteError::AmbiguousJoinError { from, to, paths } => {
+                write!(f, "Join path from {} to {} is ambiguous ({} shortest paths)", from, to, paths)
+            },
+            BuildliteError::SQLError(e, query) => {
+                let msg = &format!("{}{}", e, QueryContext::suffix(query));
                 f.write_str(msg)
             },
         }
     }
 }
-impl StdError for BuildliteError {}
+impl StdError for BuildliteError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        return match self {
+            BuildliteError::NoRowsError => None,
+            BuildliteError::TooManyRowsError => None,
+            BuildliteError::BusyError(e, _) => Some(e),
+            BuildliteError::UniqueError { error, .. } => Some(error),
+            BuildliteError::ForeignKeyError(e, _) => Some(e),
+            BuildliteError::ConstraintError { error, .. } => Some(error),
+            BuildliteError::TypeMismatchError(e, _) => Some(e),
+            BuildliteError::TimeoutError(_) => None,
+            BuildliteError::CancelledError => None,
+            BuildliteError::FullScanError { .. } => None,
+            BuildliteError::UnfilteredUpdateError => None,
+            BuildliteError::UnknownColumnError { .. } => None,
+            BuildliteError::UnknownAliasError { .. } => None,
+            BuildliteError::UnexpectedRowsError { .. } => None,
+            BuildliteError::NoJoinPathError { .. } => None,
+            BuildliteError::AmbiguousJoinError { .. } => None,
+            BuildliteError::SQLError(e, _) => Some(e),
+        };
+    }
+}
 impl<T> BuildliteErrorMatch<T, RusqliteError> for Result<T, RusqliteError> {
     fn quick_match(self) -> Result<T, BuildliteError> {
         return match self {
             Ok(s) => Ok(s),
-            Err(e) => Err(BuildliteError::SQLError(e)),
+            Err(e) => Err(BuildliteError::from(e)),
         };
     }
 }
@@ -0,0 +1,23 @@
+use crate::error::BuildliteError;
+#[derive(Clone, Copy, Default)]
+pub(crate) struct UpdateGuard {
+    pub(crate) filtered: bool,
+    pub(crate) all_rows: bool,
+    pub(crate) expect_rows: Option<usize>,
+}
+impl UpdateGuard {
+    pub(crate) fn check_filtered(&self) -> Result<(), BuildliteError> {
+        if !self.filtered && !self.all_rows {
+            return Err(BuildliteError::UnfilteredUpdateError);
+        }
+        return Ok(());
+    }
+    pub(crate) fn check_affected(&self, affected: usize) -> Result<usize, BuildliteError> {
+        return match self.expect_rows {
+            Some(expected) if expected != affected => {
+                Err(BuildliteError::UnexpectedRowsError { expected, actual: affected })
+            },
+            _ => Ok(affected),
+        };
+    }
+}
@@ -0,0 +1,66 @@
+use {
+    crate::error::{
+        BuildliteError,
+        BuildliteErrorMatch,
+    },
+    std::collections::{
+        HashMap,
+        HashSet,
+    },
+    worm::core::DbCtx,
+};
+pub(crate) fn quote<'a>(ident: &'a str) -> String {
+    return format!("\"{}\"", ident.replace('"', "\"\""));
+}
+pub(crate) fn qualify<'a>(prefix: &'a str, ident: &'a str) -> String {
+    return format!("{}.{}", quote(prefix), quote(ident));
+}
+const ROWID_ALIASES: [&'static str; 3] = [ "rowid", "oid", "_rowid_" ];
+fn load_columns<'a>(
+    db: &mut impl DbCtx,
+    schema: &'a str,
+    table: &'a str
+) -> Result<HashSet<String>, BuildliteError> {
+    let c = db.use_connection();
+    let mut stmt = c.prepare_cached("select name from pragma_table_info(?, ?)").quick_match()?;
+    let mut rows = stmt.query(worm::core::sql::params![ table, schema ]).quick_match()?;
+    let mut columns = HashSet::new();
+    while let Some(row) = rows.next().quick_match()? {
+        let name: String = row.get(0).quick_match()?;
+        columns.insert(name.to_lowercase());
+    }
+    if !columns.is_empty() {
+        for alias in ROWID_ALIASES {
+            columns.insert(alias.to_string());
+        }
+    }
+    return Ok(columns);
+}
+pub(crate) fn validate_columns(
+    db: &mut impl DbCtx,
+    columns: &[(String, String, String)]
+) -> Result<(), BuildliteError> {
+    // read from the connection being queried so that
+    // attached databases and migrations are always reflected
+    let mut loaded: HashMap<(String, String), HashSet<String>> = HashMap::new();
+    for (schema, table, column) in columns {
+        // columns on an alias that was never joined carry no schema
+        if schema.is_empty() {
+            return Err(BuildliteError::UnknownAliasError {
+                alias: table.clone(),
+            });
+        }
+        let key = (schema.to_lowercase(), table.to_lowercase());
+        if !loaded.contains_key(&key) {
+            let table_columns = load_columns(db, schema, table)?;
+            loaded.insert(key.clone(), table_columns);
+        }
+        if !loaded[&key].contains(&column.to_lowercase()) {
+            return Err(BuildliteError::UnknownColumnError {
+                table: format!("{}.{}", schema, table),
+                column: column.clone(),
+            });
+        }
+    }
+    return Ok(());
+}
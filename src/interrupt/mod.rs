@@ -0,0 +1,161 @@
+use {
+    crate::error::BuildliteError,
+    std::{
+        sync::{
+            Arc,
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    worm::core::{
+        DbCtx,
+        sql::{
+            Connection,
+            Error as RusqliteError,
+            ErrorCode,
+        },
+    },
+};
+// Number of virtual machine instructions between deadline and cancellation checks.
+const PROGRESS_OPS: i32 = 1000;
+#[derive(Clone)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+impl Default for CancelToken {
+    fn default() -> Self {
+        return CancelToken::new();
+    }
+}
+impl CancelToken {
+    pub fn new() -> Self {
+        return CancelToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+#[derive(Clone, Default)]
+pub(crate) struct Interrupt {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: Option<CancelToken>,
+}
+#[derive(Clone, Default)]
+pub(crate) struct Watch {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+    timed_out: Arc<AtomicBool>,
+}
+impl Watch {
+    fn is_interrupted(e: &BuildliteError) -> bool {
+        return match e {
+            BuildliteError::SQLError(RusqliteError::SqliteFailure(err, _), _) => {
+                err.code == ErrorCode::OperationInterrupted
+            },
+            _ => false,
+        };
+    }
+    fn is_cancelled(&self) -> bool {
+        return match &self.cancel {
+            Some(cancel) => cancel.is_cancelled(),
+            None => false,
+        };
+    }
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        return self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+    }
+    pub(crate) fn check(&self) -> Result<(), BuildliteError> {
+        if self.is_cancelled() {
+            return Err(BuildliteError::CancelledError);
+        }
+        if self.remaining() == Some(Duration::ZERO) {
+            self.timed_out.store(true, Ordering::SeqCst);
+            return Err(BuildliteError::TimeoutError(self.timeout.unwrap()));
+        }
+        return Ok(());
+    }
+    pub(crate) fn classify(&self, e: BuildliteError) -> BuildliteError {
+        if !Self::is_interrupted(&e) {
+            return e;
+        }
+        if self.timed_out.load(Ordering::SeqCst) {
+            return BuildliteError::TimeoutError(self.timeout.unwrap());
+        }
+        if self.is_cancelled() {
+            return BuildliteError::CancelledError;
+        }
+        return e;
+    }
+}
+pub(crate) struct Watched<'w, D> where D: DbCtx {
+    db: &'w mut D,
+    installed: bool,
+}
+impl<'w, D> DbCtx for Watched<'w, D> where D: DbCtx {
+    fn use_connection(&mut self) -> &mut Connection {
+        return self.db.use_connection();
+    }
+}
+impl<'w, D> Drop for Watched<'w, D> where D: DbCtx {
+    fn drop(&mut self) {
+        // also reached when the closure panics, so no stale deadline stays on the connection
+        if self.installed {
+            self.db.use_connection().progress_handler(0, None::<fn() -> bool>);
+        }
+    }
+}
+impl Interrupt {
+    pub(crate) fn watch(&self) -> Watch {
+        return Watch {
+            timeout: self.timeout,
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            cancel: self.cancel.clone(),
+            timed_out: Arc::new(AtomicBool::new(false)),
+        };
+    }
+    pub(crate) fn run<D, F, R>(&self, db: &mut D, f: F) -> Result<R, BuildliteError>
+    where
+        D: DbCtx,
+        F: FnOnce(&mut Watched<D>, &Watch) -> Result<R, BuildliteError>
+    {
+        let watch = self.watch();
+        let installed = self.timeout.is_some() || self.cancel.is_some();
+        if installed {
+            watch.check()?;
+            let deadline = watch.deadline;
+            let cancelled = self.cancel.as_ref().map(|cancel| cancel.cancelled.clone());
+            let timed_out = watch.timed_out.clone();
+            db.use_connection().progress_handler(PROGRESS_OPS, Some(move || {
+                if let Some(cancelled) = &cancelled {
+                    if cancelled.load(Ordering::SeqCst) {
+                        return true;
+                    }
+                }
+                if let Some(deadline) = deadline {
+                    if Instant::now() >= deadline {
+                        timed_out.store(true, Ordering::SeqCst);
+                        return true;
+                    }
+                }
+                return false;
+            }));
+        }
+        let mut watched = Watched {
+            db,
+            installed,
+        };
+        return f(&mut watched, &watch).map_err(|e| watch.classify(e));
+    }
+}
@@ -1,6 +1,56 @@
 mod query;
 pub use query::Query;
+mod transaction;
+pub use transaction::{
+    in_transaction,
+    Transaction,
+};
+mod retry;
+pub use retry::{
+    in_transaction_retry,
+    RetryPolicy,
+};
+mod interrupt;
+pub use interrupt::CancelToken;
+mod observe;
+pub use observe::{
+    clear_query_observer,
+    set_query_observer,
+    QueryEvent,
+    QueryObserver,
+};
+mod param;
+pub use param::{
+    register_sensitive_columns,
+    Param,
+    SensitiveColumns,
+};
+mod guard;
+mod ident;
+mod compiled;
+pub use compiled::CompiledQuery;
+mod rows;
+pub use rows::RowIter;
+mod scope;
+pub use scope::Scope;
+mod active;
+pub use active::ActiveModels;
+mod path;
+pub use path::{
+    JoinPath,
+    Relations,
+};
+mod plan;
+pub use plan::{
+    PlanNode,
+    QueryPlan,
+};
 mod error;
-pub use error::BuildliteError;
+pub use error::{
+    BuildliteError,
+    BuildliteErrorMatch,
+    ConstraintKind,
+    QueryContext,
+};
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,80 @@
+use {
+    crate::{
+        error::BuildliteError,
+        param::Param,
+    },
+    std::{
+        sync::{
+            Arc,
+            RwLock,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+pub struct QueryEvent<'a> {
+    pub sql: &'a str,
+    pub params: Vec<String>,
+    pub rows: Option<usize>,
+    pub elapsed: Duration,
+    pub error: Option<&'a BuildliteError>,
+}
+pub trait QueryObserver: Send + Sync {
+    fn on_query(&self, event: &QueryEvent);
+}
+impl<F> QueryObserver for F where F: Fn(&QueryEvent) + Send + Sync {
+    fn on_query(&self, event: &QueryEvent) {
+        self(event);
+    }
+}
+static GLOBAL_OBSERVER: RwLock<Option<Arc<dyn QueryObserver>>> = RwLock::new(None);
+pub fn set_query_observer(observer: Arc<dyn QueryObserver>) {
+    *GLOBAL_OBSERVER.write().unwrap() = Some(observer);
+}
+pub fn clear_query_observer() {
+    *GLOBAL_OBSERVER.write().unwrap() = None;
+}
+#[derive(Clone, Default)]
+pub(crate) struct Observer {
+    pub(crate) observer: Option<Arc<dyn QueryObserver>>,
+    pub(crate) redact: bool,
+}
+impl Observer {
+    pub(crate) fn render_params(&self, params: &[&Param], sensitive: &[bool]) -> Vec<String> {
+        return params.iter()
+            .zip(sensitive)
+            .map(|(param, sensitive)| param.to_redacted_literal(self.redact || *sensitive))
+            .collect();
+    }
+    pub(crate) fn run<F, R>(
+        &self,
+        sql: &str,
+        params: &[&Param],
+        sensitive: &[bool],
+        rows: fn(&R) -> Option<usize>,
+        f: F
+    ) -> Result<R, BuildliteError>
+    where
+        F: FnOnce() -> Result<R, BuildliteError>
+    {
+        let global = GLOBAL_OBSERVER.read().unwrap().clone();
+        if self.observer.is_none() && global.is_none() {
+            return f();
+        }
+        let start = Instant::now();
+        let res = f();
+        let event = QueryEvent {
+            sql,
+            params: self.render_params(params, sensitive),
+            rows: res.as_ref().ok().and_then(rows),
+            elapsed: start.elapsed(),
+            error: res.as_ref().err(),
+        };
+        for observer in self.observer.iter().chain(global.iter()) {
+            observer.on_query(&event);
+        }
+        return res;
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::RwLock;
+use worm::core::{
+    DbModel,
+    sql::{
+        Error as RusqliteError,
+        Result as RusqliteResult,
+        ToSql,
+        types::{
+            ToSqlOutput,
+            Value,
+            ValueRef,
+        },
+    },
+};
+pub(crate) const REDACTED: &'static str = "[redacted]";
+pub trait SensitiveColumns: DbModel {
+    const SENSITIVE: &'static [&'static str];
+}
+// (db, table, column) of every column registered as sensitive for all queries
+static SENSITIVE_COLUMNS: RwLock<Vec<(String, String, String)>> = RwLock::new(Vec::new());
+pub fn register_sensitive_columns<U>() where U: SensitiveColumns {
+    let mut registered = SENSITIVE_COLUMNS.write().unwrap();
+    for column in U::SENSITIVE {
+        let exists = registered.iter()
+            .any(|(db, table, registered_column)| {
+                return db == U::DB && table == U::TABLE && registered_column == column;
+            });
+        if !exists {
+            registered.push((U::DB.to_string(), U::TABLE.to_string(), column.to_string()));
+        }
+    }
+}
+pub(crate) fn is_registered_sensitive(db: &str, table: &str, column: &str) -> bool {
+    return SENSITIVE_COLUMNS.read().unwrap().iter()
+        .any(|(sensitive_db, sensitive_table, sensitive_column)| {
+            return sensitive_db == db && sensitive_table == table
+                && sensitive_column.eq_ignore_ascii_case(column);
+        });
+}
+#[derive(Clone)]
+pub enum Param<'query> {
+    Borrowed(ValueRef<'query>),
+    Owned(Value),
+    Invalid(String),
+}
+impl<'query> Param<'query> {
+    pub fn owned(value: impl Into<Value>) -> Self {
+        return Param::Owned(value.into());
+    }
+    fn value_literal(value: ValueRef) -> String {
+        return match value {
+            ValueRef::Null => String::from("null"),
+            ValueRef::Integer(i) => i.to_string(),
+            ValueRef::Real(f) if f.is_nan() => String::from("null"),
+            ValueRef::Real(f) if f.is_infinite() => match f.is_sign_positive() {
+                true => String::from("9e999"),
+                false => String::from("-9e999"),
+            },
+            ValueRef::Real(f) => format!("{:?}", f),
+            ValueRef::Text(t) => {
+                format!("'{}'", String::from_utf8_lossy(t).replace('\'', "''"))
+            },
+            ValueRef::Blob(b) => {
+                let hex = b.iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>();
+                format!("x'{}'", hex)
+            },
+        };
+    }
+    pub(crate) fn to_redacted_literal(&self, sensitive: bool) -> String {
+        return match sensitive {
+            true => String::from(REDACTED),
+            false => self.to_literal(),
+        };
+    }
+    pub(crate) fn to_literal(&self) -> String {
+        return match self.to_sql() {
+            Ok(ToSqlOutput::Borrowed(value)) => Self::value_literal(value),
+            Ok(ToSqlOutput::Owned(value)) => Self::value_literal((&value).into()),
+            Ok(_) => String::from("?"),
+            Err(_) => String::from("?"),
+        };
+    }
+}
+impl<'query> ToSql for Param<'query> {
+    fn to_sql(&self) -> RusqliteResult<ToSqlOutput<'_>> {
+        return match self {
+            Param::Borrowed(value) => Ok(ToSqlOutput::Borrowed(*value)),
+            Param::Owned(value) => value.to_sql(),
+            Param::Invalid(message) => {
+                Err(RusqliteError::ToSqlConversionFailure(message.clone().into()))
+            },
+        };
+    }
+}
+impl<'query> From<&'query dyn ToSql> for Param<'query> {
+    fn from(value: &'query dyn ToSql) -> Self {
+        // converted up front so that borrowed values never need to be Sync
+        return match value.to_sql() {
+            Ok(ToSqlOutput::Borrowed(value)) => Param::Borrowed(value),
+            Ok(ToSqlOutput::Owned(value)) => Param::Owned(value),
+            Ok(_) => Param::Invalid(String::from("unsupported parameter output")),
+            Err(e) => Param::Invalid(e.to_string()),
+        };
+    }
+}
+impl<'query, V> From<&'query V> for Param<'query> where V: ToSql {
+    fn from(value: &'query V) -> Self {
+        return Param::from(value as &dyn ToSql);
+    }
+}
+impl<'query> From<Value> for Param<'query> {
+    fn from(value: Value) -> Self {
+        return Param::Owned(value);
+    }
+}
+impl<'query> From<&'query str> for Param<'query> {
+    fn from(value: &'query str) -> Self {
+        return Param::Borrowed(ValueRef::Text(value.as_bytes()));
+    }
+}
+pub(crate) fn inline_params(sql: &str, literals: Vec<String>) -> String {
+    let mut literals = literals.into_iter();
+    let mut inlined = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (None, '?') => {
+                match literals.next() {
+                    Some(literal) => inlined.push_str(&literal),
+                    None => inlined.push(c),
+                }
+                continue;
+            },
+            (None, '\'') | (None, '"') | (None, '`') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => {},
+        }
+        inlined.push(c);
+    }
+    return inlined;
+}
@@ -0,0 +1,197 @@
+use {
+    crate::{
+        error::BuildliteError,
+        Query,
+    },
+    std::collections::{
+        HashMap,
+        VecDeque,
+    },
+    worm::core::{
+        ForeignKey,
+        PrimaryKeyModel,
+    },
+};
+pub trait JoinPath<T> where T: PrimaryKeyModel {
+    fn join_path<'query>(query: Query<'query, T>) -> Query<'query, T>;
+}
+impl<T, A> JoinPath<T> for (A,)
+where
+    T: PrimaryKeyModel + ForeignKey<A>,
+    A: PrimaryKeyModel,
+{
+    fn join_path<'query>(query: Query<'query, T>) -> Query<'query, T> {
+        return query.join_fk::<A>();
+    }
+}
+impl<T, A, B> JoinPath<T> for (A, B)
+where
+    T: PrimaryKeyModel + ForeignKey<A>,
+    A: PrimaryKeyModel + ForeignKey<B>,
+    B: PrimaryKeyModel,
+{
+    fn join_path<'query>(query: Query<'query, T>) -> Query<'query, T> {
+        return query.join_fk::<A>()
+            .join_fk_from::<A, B>();
+    }
+}
+impl<T, A, B, C> JoinPath<T> for (A, B, C)
+where
+    T: PrimaryKeyModel + ForeignKey<A>,
+    A: PrimaryKeyModel + ForeignKey<B>,
+    B: PrimaryKeyModel + ForeignKey<C>,
+    C: PrimaryKeyModel,
+{
+    fn join_path<'query>(query: Query<'query, T>) -> Query<'query, T> {
+        return query.join_fk::<A>()
+            .join_fk_from::<A, B>()
+            .join_fk_from::<B, C>();
+    }
+}
+impl<T, A, B, C, D> JoinPath<T> for (A, B, C, D)
+where
+    T: PrimaryKeyModel + ForeignKey<A>,
+    A: PrimaryKeyModel + ForeignKey<B>,
+    B: PrimaryKeyModel + ForeignKey<C>,
+    C: PrimaryKeyModel + ForeignKey<D>,
+    D: PrimaryKeyModel,
+{
+    fn join_path<'query>(query: Query<'query, T>) -> Query<'query, T> {
+        return query.join_fk::<A>()
+            .join_fk_from::<A, B>()
+            .join_fk_from::<B, C>()
+            .join_fk_from::<C, D>();
+    }
+}
+#[derive(Clone, Copy)]
+struct Table {
+    db: &'static str,
+    table: &'static str,
+    alias: &'static str,
+    primary_key: &'static str,
+}
+impl Table {
+    fn of<U>() -> Self where U: PrimaryKeyModel {
+        return Table {
+            db: U::DB,
+            table: U::TABLE,
+            alias: U::ALIAS,
+            primary_key: U::PRIMARY_KEY,
+        };
+    }
+}
+#[derive(Clone, Copy)]
+struct Relation {
+    from: Table,
+    foreign_key: &'static str,
+    to: Table,
+}
+pub(crate) struct Hop {
+    pub(crate) db: &'static str,
+    pub(crate) table: &'static str,
+    pub(crate) alias: &'static str,
+    pub(crate) column: &'static str,
+    pub(crate) from: &'static str,
+    pub(crate) from_column: &'static str,
+}
+impl Relation {
+    fn hop_from(&self, alias: &str) -> Option<(&'static str, Hop)> {
+        // foreign keys can be followed in either direction
+        if self.from.alias == alias {
+            return Some((self.to.alias, Hop {
+                db: self.to.db,
+                table: self.to.table,
+                alias: self.to.alias,
+                column: self.to.primary_key,
+                from: self.from.alias,
+                from_column: self.foreign_key,
+            }));
+        }
+        if self.to.alias == alias {
+            return Some((self.from.alias, Hop {
+                db: self.from.db,
+                table: self.from.table,
+                alias: self.from.alias,
+                column: self.foreign_key,
+                from: self.to.alias,
+                from_column: self.to.primary_key,
+            }));
+        }
+        return None;
+    }
+}
+#[derive(Clone, Default)]
+pub struct Relations {
+    relations: Vec<Relation>,
+}
+impl Relations {
+    pub fn new() -> Self {
+        return Relations::default();
+    }
+    pub fn add<V, U>(mut self) -> Self
+    where
+        V: PrimaryKeyModel + ForeignKey<U>,
+        U: PrimaryKeyModel,
+    {
+        self.relations.push(Relation {
+            from: Table::of::<V>(),
+            foreign_key: <V as ForeignKey<U>>::FOREIGN_KEY,
+            to: Table::of::<U>(),
+        });
+        return self;
+    }
+    pub(crate) fn resolve<'a>(&self, from: &'a str, to: &'a str) -> Result<Vec<Hop>, BuildliteError> {
+        // breadth first, counting the shortest paths into each table so that ties can be refused
+        let mut found: HashMap<&str, (usize, usize, Option<usize>)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        found.insert(from, (0, 1, None));
+        queue.push_back(from);
+        while let Some(alias) = queue.pop_front() {
+            let (depth, paths, _) = found[alias];
+            for (index, relation) in self.relations.iter().enumerate() {
+                let next = match relation.hop_from(alias) {
+                    Some((next, _)) => next,
+                    None => continue,
+                };
+                match found.get_mut(next) {
+                    Some(entry) => {
+                        if entry.0 == depth + 1 {
+                            entry.1 += paths;
+                        }
+                    },
+                    None => {
+                        found.insert(next, (depth + 1, paths, Some(index)));
+                        queue.push_back(next);
+                    },
+                }
+            }
+        }
+        let paths = match found.get(to) {
+            Some((_, paths, _)) => *paths,
+            None => 0,
+        };
+        if paths == 0 {
+            return Err(BuildliteError::NoJoinPathError {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        if paths > 1 {
+            return Err(BuildliteError::AmbiguousJoinError {
+                from: from.to_string(),
+                to: to.to_string(),
+                paths,
+            });
+        }
+        let mut hops = Vec::new();
+        let mut alias = to;
+        while let Some(index) = found[alias].2 {
+            let relation = &self.relations[index];
+            let prev = if relation.to.alias == alias { relation.from.alias } else { relation.to.alias };
+            hops.push(relation.hop_from(prev).unwrap().1);
+            alias = prev;
+        }
+        hops.reverse();
+        return Ok(hops);
+    }
+}
@@ -0,0 +1,134 @@
+use {
+    crate::{
+        error::{
+            BuildliteError,
+            BuildliteErrorMatch,
+        },
+        ident::qualify,
+        param::Param,
+    },
+    worm::core::DbCtx,
+};
+#[derive(Clone, Debug)]
+pub struct PlanNode {
+    pub id: i64,
+    pub detail: String,
+    pub children: Vec<PlanNode>,
+}
+impl PlanNode {
+    pub fn is_scan(&self) -> bool {
+        return self.detail.starts_with("SCAN ");
+    }
+    pub fn is_search(&self) -> bool {
+        return self.detail.starts_with("SEARCH ");
+    }
+    pub fn uses_index(&self) -> bool {
+        return self.detail.contains(" USING ");
+    }
+    pub fn table(&self) -> Option<&str> {
+        if !self.is_scan() && !self.is_search() {
+            return None;
+        }
+        let mut words = self.detail.split(' ').skip(1);
+        let table = match words.next() {
+            Some("TABLE") => words.next(),
+            table => table,
+        };
+        // older versions of sqlite render "TABLE name AS alias"
+        return match (words.next(), words.next()) {
+            (Some("AS"), Some(alias)) => Some(alias),
+            _ => table,
+        };
+    }
+}
+#[derive(Clone, Debug)]
+pub struct QueryPlan {
+    pub nodes: Vec<PlanNode>,
+}
+impl QueryPlan {
+    fn build(rows: &[(i64, i64, String)], parent: i64) -> Vec<PlanNode> {
+        return rows.iter()
+            .filter(|row| row.1 == parent)
+            .map(|row| PlanNode {
+                id: row.0,
+                detail: row.2.clone(),
+                children: Self::build(rows, row.0),
+            })
+            .collect();
+    }
+    fn collect<'a>(nodes: &'a [PlanNode], out: &mut Vec<&'a PlanNode>) {
+        for node in nodes {
+            out.push(node);
+            Self::collect(&node.children, out);
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &PlanNode> {
+        let mut nodes = Vec::new();
+        Self::collect(&self.nodes, &mut nodes);
+        return nodes.into_iter();
+    }
+    pub fn full_scans(&self) -> Vec<&PlanNode> {
+        return self.iter()
+            .filter(|node| node.is_scan() && !node.uses_index())
+            .collect();
+    }
+}
+pub(crate) fn explain(
+    db: &mut impl DbCtx,
+    sql: &str,
+    params: &[&Param]
+) -> Result<QueryPlan, BuildliteError> {
+    let param = worm::core::sql::params_from_iter(params.iter());
+    let c = db.use_connection();
+    let mut stmt = c.prepare(&format!("explain query plan {}", sql)).quick_match()?;
+    let mut rows = stmt.query(param).quick_match()?;
+    let mut plan_rows = Vec::new();
+    while let Some(row) = rows.next().quick_match()? {
+        plan_rows.push((
+            row.get(0).quick_match()?,
+            row.get(1).quick_match()?,
+            row.get(3).quick_match()?,
+        ));
+    }
+    return Ok(QueryPlan { nodes: QueryPlan::build(&plan_rows, 0) });
+}
+pub(crate) fn check_scans(
+    db: &mut impl DbCtx,
+    plan: &QueryPlan,
+    tables: &[(String, String, String)],
+    max_rows: usize
+) -> Result<(), BuildliteError> {
+    for node in plan.full_scans() {
+        let name = match node.table() {
+            Some(name) => name,
+            None => continue,
+        };
+        // ctes and subqueries appear under their own names and are not counted
+        let found = tables.iter().find(|(alias, schema, table)| {
+            return alias.eq_ignore_ascii_case(name)
+                || table.eq_ignore_ascii_case(name)
+                || format!("{}.{}", schema, table).eq_ignore_ascii_case(name);
+        });
+        let (schema, table) = match found {
+            Some((_, schema, table)) => (schema.as_str(), table.as_str()),
+            None => continue,
+        };
+        // bounded count so that the guard never scans more than it allows
+        let c = db.use_connection();
+        let rows: i64 = c.query_row(
+            &format!(
+                "select count(*) from (select 1 from {} limit {})",
+                qualify(schema, table), max_rows + 1
+            ),
+            worm::core::sql::params![],
+            |row| row.get(0)
+        ).quick_match()?;
+        if rows as usize > max_rows {
+            return Err(BuildliteError::FullScanError {
+                table: format!("{}.{}", schema, table),
+                detail: node.detail.clone(),
+            });
+        }
+    }
+    return Ok(());
+}
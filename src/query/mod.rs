@@ -1,20 +1,94 @@
 use {
-    crate::error::{
-        BuildliteError,
-        BuildliteErrorMatch,
+    crate::{
+        error::{
+            BuildliteError,
+            BuildliteErrorMatch,
+        },
+        compiled::CompiledQuery,
+        guard::UpdateGuard,
+        ident::{
+            self,
+            quote,
+            qualify,
+        },
+        interrupt::{
+            CancelToken,
+            Interrupt,
+        },
+        observe::{
+            Observer,
+            QueryObserver,
+        },
+        param::{
+            inline_params,
+            is_registered_sensitive,
+            Param,
+            SensitiveColumns,
+        },
+        active::ActiveModels,
+        path::{
+            JoinPath,
+            Relations,
+        },
+        plan::{
+            self,
+            QueryPlan,
+        },
+        retry::RetryPolicy,
+        rows::RowIter,
+        scope::Scope,
+        transaction::in_savepoint,
+    },
+    std::{
+        collections::HashMap,
+        sync::Arc,
+        time::Duration,
     },
-    std::collections::HashMap,
     worm::core::{
+        ActiveFlagModel,
         DbCtx,
         ForeignKey,
+        PrimaryKey,
         PrimaryKeyModel,
-        sql::ToSql,
+        UniqueNameModel,
+        sql::{
+            Error as RusqliteError,
+            Row,
+            types::FromSql,
+        },
     },
 };
-enum QueryType {
+// stays well under the smallest default limit on bound variables in sqlite
+const MAX_IN_PARAMS: usize = 500;
+#[derive(Clone, Copy)]
+pub(crate) enum QueryType {
     Select,
     Update,
 }
+#[derive(Clone, Default)]
+struct OrderMods {
+    collate: Option<String>,
+    nulls: Option<&'static str>,
+}
+#[derive(Clone)]
+struct OrderTerm {
+    expr: String,
+    dir: &'static str,
+    mods: OrderMods,
+}
+impl OrderTerm {
+    fn to_sql(&self) -> String {
+        let mut sql = self.expr.clone();
+        if self.mods.collate.is_some() {
+            sql.push_str(&format!(" collate {}", quote(self.mods.collate.as_ref().unwrap())));
+        }
+        sql.push_str(&format!(" {}", self.dir));
+        if self.mods.nulls.is_some() {
+            sql.push_str(&format!(" {}", self.mods.nulls.unwrap()));
+        }
+        return sql;
+    }
+}
 pub struct Query<'query, T> {
     query_type: QueryType,
     select: String,
@@ -22,44 +96,193 @@ pub struct Query<'query, T> {
     set: Option<String>,
     from: String,
     join: Option<String>,
+    join_conj: bool,
     clause: Option<String>,
-    orderby: Option<String>,
+    conj: Option<&'static str>,
+    skipped: bool,
+    orderby: Vec<OrderTerm>,
+    limit: Option<usize>,
+    active: Vec<(String, String, String)>,
+    include_inactive: bool,
+    retry: RetryPolicy,
+    interrupt: Interrupt,
+    observer: Observer,
     _value: Option<T>,
-    select_params: HashMap<String, Box<&'query dyn ToSql>>,
-    update_params: HashMap<String, Box<&'query dyn ToSql>>,
+    select_params: HashMap<String, Param<'query>>,
+    update_params: HashMap<String, Param<'query>>,
+    param_columns: HashMap<String, (String, String)>,
+    sensitive: Vec<(String, String)>,
+    sensitive_tables: Vec<(String, String, String)>,
+    tables: Vec<(String, String, String)>,
+    columns: Vec<(String, String)>,
+    scan_guard: Option<usize>,
+    guard: UpdateGuard,
+}
+impl<'query, T> Clone for Query<'query, T> {
+    fn clone(&self) -> Self {
+        return Query {
+            query_type: self.query_type,
+            select: self.select.clone(),
+            update: self.update.clone(),
+            set: self.set.clone(),
+            from: self.from.clone(),
+            join: self.join.clone(),
+            join_conj: self.join_conj,
+            clause: self.clause.clone(),
+            conj: self.conj,
+            skipped: self.skipped,
+            orderby: self.orderby.clone(),
+            limit: self.limit,
+            active: self.active.clone(),
+            include_inactive: self.include_inactive,
+            retry: self.retry,
+            interrupt: self.interrupt.clone(),
+            observer: self.observer.clone(),
+            _value: None,
+            select_params: self.select_params.clone(),
+            update_params: self.update_params.clone(),
+            param_columns: self.param_columns.clone(),
+            sensitive: self.sensitive.clone(),
+            sensitive_tables: self.sensitive_tables.clone(),
+            tables: self.tables.clone(),
+            columns: self.columns.clone(),
+            scan_guard: self.scan_guard,
+            guard: self.guard,
+        };
+    }
 }
 impl<'query, T> Query<'query, T> where T: PrimaryKeyModel {
     pub fn select() -> Self {
         return Query {
             query_type: QueryType::Select,
-            select: format!("select {}.*", T::ALIAS),
+            select: format!("select {}.*", quote(T::ALIAS)),
             update: String::new(),
             set: None,
-            from: format!("from {}.{} as {}", T::DB, T::TABLE, T::ALIAS),
+            from: format!("from {} as {}", qualify(T::DB, T::TABLE), quote(T::ALIAS)),
             join: None,
+            join_conj: false,
             clause: None,
-            orderby: None,
+            conj: None,
+            skipped: false,
+            orderby: Vec::new(),
+            limit: None,
+            active: Vec::new(),
+            include_inactive: false,
+            retry: RetryPolicy::default(),
+            interrupt: Interrupt::default(),
+            observer: Observer::default(),
             _value: None,
             select_params: HashMap::new(),
             update_params: HashMap::new(),
+            param_columns: HashMap::new(),
+            sensitive: Vec::new(),
+            sensitive_tables: Vec::new(),
+            tables: vec![ (T::ALIAS.to_string(), T::DB.to_string(), T::TABLE.to_string()) ],
+            columns: Vec::new(),
+            scan_guard: None,
+            guard: UpdateGuard::default(),
         };
     }
     pub fn update() -> Self {
         return Query {
             query_type: QueryType::Update,
-            select: format!("select {}.*", T::ALIAS),
-            update: format!("update {}.{}", T::DB, T::TABLE),
+            select: format!("select {}.*", quote(T::ALIAS)),
+            update: format!("update {}", qualify(T::DB, T::TABLE)),
             set: None,
             from: String::new(),
             join: None,
+            join_conj: false,
             clause: None,
-            orderby: None,
+            conj: None,
+            skipped: false,
+            orderby: Vec::new(),
+            limit: None,
+            active: Vec::new(),
+            include_inactive: false,
+            retry: RetryPolicy::default(),
+            interrupt: Interrupt::default(),
+            observer: Observer::default(),
             _value: None,
             select_params: HashMap::new(),
             update_params: HashMap::new(),
+            param_columns: HashMap::new(),
+            sensitive: Vec::new(),
+            sensitive_tables: Vec::new(),
+            tables: vec![ (T::ALIAS.to_string(), T::DB.to_string(), T::TABLE.to_string()) ],
+            columns: Vec::new(),
+            scan_guard: None,
+            guard: UpdateGuard::default(),
         };
     }
-    pub fn set<'a>(mut self, column: &'a str, value: &'query dyn ToSql) -> Self {
+    pub fn soft_delete() -> Self
+    where
+        T: ActiveFlagModel,
+    {
+        return Self::update()
+            .set(T::ACTIVE, Param::owned(false));
+    }
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        return self;
+    }
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.interrupt.timeout = Some(timeout);
+        return self;
+    }
+    pub fn cancel_token(mut self, token: &CancelToken) -> Self {
+        self.interrupt.cancel = Some(token.clone());
+        return self;
+    }
+    pub fn observe(mut self, observer: Arc<dyn QueryObserver>) -> Self {
+        self.observer.observer = Some(observer);
+        return self;
+    }
+    pub fn redact_params(mut self) -> Self {
+        self.observer.redact = true;
+        return self;
+    }
+    pub fn sensitive<'a>(self, column: &'a str) -> Self {
+        return self.sensitive_as(T::ALIAS, column);
+    }
+    pub fn sensitive_as<'a>(mut self, alias: &'a str, column: &'a str) -> Self {
+        self.sensitive.push((alias.to_string(), column.to_string()));
+        return self;
+    }
+    pub fn sensitive_columns<U>(mut self) -> Self
+    where
+        U: SensitiveColumns
+    {
+        for column in U::SENSITIVE {
+            self.sensitive_tables.push((U::DB.to_string(), U::TABLE.to_string(), column.to_string()));
+        }
+        return self;
+    }
+    pub fn scan_guard(mut self, max_rows: usize) -> Self {
+        self.scan_guard = Some(max_rows);
+        return self;
+    }
+    pub fn all_rows(mut self) -> Self {
+        self.guard.all_rows = true;
+        return self;
+    }
+    pub fn expect_rows(mut self, rows: usize) -> Self {
+        self.guard.expect_rows = Some(rows);
+        return self;
+    }
+    pub fn active_only<M>(mut self) -> Self
+    where
+        M: ActiveModels,
+    {
+        for (db, table, column) in M::active_columns() {
+            self.active.push((db.to_string(), table.to_string(), column.to_string()));
+        }
+        return self;
+    }
+    pub fn with_inactive(mut self) -> Self {
+        self.include_inactive = true;
+        return self;
+    }
+    pub fn set<'a>(mut self, column: &'a str, value: impl Into<Param<'query>>) -> Self {
         let dlim;
         let set;
         if self.set.is_none() {
@@ -71,18 +294,22 @@ impl<'query, T> Query<'query, T> where T: PrimaryKeyModel {
         }
         let param_num = self.select_params.len() + self.update_params.len();
         let param_name = format!(":param{}", param_num);
-        self.update_params.insert(param_name.clone(), Box::new(value));
+        self.update_params.insert(param_name.clone(), value.into());
+        self.param_columns.insert(param_name.clone(), (T::ALIAS.to_string(), column.to_string()));
+        self.columns.push((T::ALIAS.to_string(), column.to_string()));
         self.set = Some(format!(
             "{}{}{} = {}",
-            set, dlim, column, param_name
+            set, dlim, quote(column), param_name
         ));
         return self;
     }
-    pub fn join_fk<U>(mut self) -> Self
-    where
-        U: PrimaryKeyModel,
-        T: ForeignKey<U>,
-    {
+    fn push_join<'a>(
+        mut self,
+        db: &'a str,
+        table: &'a str,
+        alias: &'a str,
+        clause: String
+    ) -> Self {
         match self.query_type {
             QueryType::Select => {},
             QueryType::Update => panic!("Update-From is not yet supported"),
@@ -96,143 +323,140 @@ impl<'query, T> Query<'query, T> where T: PrimaryKeyModel {
             join_str = self.join.unwrap();
             dlim = String::from(" ");
         }
-        self.join = Some(
-            format!(
-                "{}{}join {}.{} as {} on {}.{} = {}.{}",
-                join_str, dlim,
-                U::DB, U::TABLE, U::ALIAS,
-                T::ALIAS, T::FOREIGN_KEY, U::ALIAS, U::PRIMARY_KEY,
-            )
-        );
+        self.join = Some(format!("{}{}{}", join_str, dlim, clause));
+        self.join_conj = false;
+        self.tables.push((alias.to_string(), db.to_string(), table.to_string()));
         return self;
     }
-    fn filter_join_fk<'a, U>(
+    fn join_fk_column<'a, U>(
         mut self,
-        op: &'a str,
+        from: &'a str,
         column: &'a str,
-        value: &'query dyn ToSql
+        alias: &'a str,
     ) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>,
     {
-        match self.query_type {
-            QueryType::Select => {},
-            QueryType::Update => panic!("Update-From is not yet supported"),
-        }
-        let join_str;
-        let dlim;
-        if self.join.is_none() {
-            panic!("Cannot add another join constraint when there is no join");
-        } else {
-            join_str = self.join.unwrap();
-            dlim = String::from(" ");
-        }
-        let param_num = self.select_params.len() + self.update_params.len();
-        let param_name = format!(":param{}", param_num);
-        self.select_params.insert(param_name.clone(), Box::new(value));
-        self.join = Some(
-            format!(
-                "{}{}{}.{} {} {}",
-                join_str, dlim,
-                U::ALIAS, column,
-                op, param_name,
-            )
+        let clause = format!(
+            "join {} as {} on {} = {}",
+            qualify(U::DB, U::TABLE), quote(alias),
+            qualify(from, column), qualify(alias, U::PRIMARY_KEY),
         );
-        return self;
+        self.columns.push((from.to_string(), column.to_string()));
+        return self.push_join(U::DB, U::TABLE, alias, clause);
     }
-    pub fn join_fk_eq<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn join_fk<U>(self) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>
+        T: ForeignKey<U>,
     {
-        return self.filter_join_fk::<U>("=", column, value);
+        return self.join_fk_column::<U>(T::ALIAS, T::FOREIGN_KEY, U::ALIAS);
     }
-    pub fn join_fk_ne<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn join_fk_as<'a, U>(self, alias: &'a str) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>
+        T: ForeignKey<U>,
     {
-        return self.filter_join_fk::<U>("!=", column, value);
+        return self.join_fk_column::<U>(T::ALIAS, T::FOREIGN_KEY, alias);
     }
-    pub fn join_fk_gt<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn join_fk_by<'a, U>(self, column: &'a str) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>
     {
-        return self.filter_join_fk::<U>(">", column, value);
+        return self.join_fk_column::<U>(T::ALIAS, column, U::ALIAS);
     }
-    pub fn join_fk_lt<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn join_fk_by_as<'a, U>(self, column: &'a str, alias: &'a str) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>
     {
-        return self.filter_join_fk::<U>("<", column, value);
+        return self.join_fk_column::<U>(T::ALIAS, column, alias);
     }
-    pub fn join_fk_ge<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn join_fk_from<V, U>(self) -> Self
     where
         U: PrimaryKeyModel,
-        T: ForeignKey<U>
+        V: ForeignKey<U>,
     {
-        return self.filter_join_fk::<U>(">=", column, value);
+        if !self.tables.iter().any(|(alias, _, _)| alias == V::ALIAS) {
+            panic!("Cannot join from {} before it is joined", V::ALIAS);
+        }
+        return self.join_fk_column::<U>(V::ALIAS, V::FOREIGN_KEY, U::ALIAS);
     }
-    pub fn join_fk_le<'a, U>(
-        self,
-        column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
+    pub fn scope<S>(self, scope: S) -> Self
     where
-        U: PrimaryKeyModel,
-        T: ForeignKey<U>
+        S: Scope<'query, T>,
     {
-        return self.filter_join_fk::<U>("<=", column, value);
+        return scope.apply(self);
     }
-    pub fn join<U>(mut self) -> Self
+    pub fn join_path<P>(self) -> Self
     where
-        U: ForeignKey<T>
+        P: JoinPath<T>,
     {
-        match self.query_type {
-            QueryType::Select => {},
-            QueryType::Update => panic!("Update-From is not yet supported"),
-        }
-        let join_str;
-        let dlim;
-        if self.join.is_none() {
-            join_str = String::new();
-            dlim = String::new();
-        } else {
-            join_str = self.join.unwrap();
-            dlim = String::from(" ");
+        return P::join_path(self);
+    }
+    pub fn join_resolved<U>(mut self, relations: &Relations) -> Result<Self, BuildliteError>
+    where
+        U: PrimaryKeyModel,
+    {
+        for hop in relations.resolve(T::ALIAS, U::ALIAS)? {
+            if self.tables.iter().any(|(alias, _, _)| alias == hop.alias) {
+                continue;
+            }
+            let clause = format!(
+                "join {} as {} on {} = {}",
+                qualify(hop.db, hop.table), quote(hop.alias),
+                qualify(hop.from, hop.from_column), qualify(hop.alias, hop.column),
+            );
+            self.columns.push((hop.from.to_string(), hop.from_column.to_string()));
+            self = self.push_join(hop.db, hop.table, hop.alias, clause);
         }
-        self.join = Some(
-            format!(
-                "{}{}join {}.{} as {} on {}.{} = {}.{}",
-                join_str, dlim,
-                U::DB, U::TABLE, U::ALIAS,
-                T::ALIAS, T::PRIMARY_KEY, U::ALIAS, U::FOREIGN_KEY
-            )
+        return Ok(self);
+    }
+    pub fn join_through<J, U>(self) -> Self
+    where
+        U: PrimaryKeyModel,
+        J: ForeignKey<T> + ForeignKey<U>,
+    {
+        return self.join_column(J::DB, J::TABLE, <J as ForeignKey<T>>::FOREIGN_KEY, J::ALIAS)
+            .join_fk_from::<J, U>();
+    }
+    fn join_column<'a>(
+        mut self,
+        db: &'a str,
+        table: &'a str,
+        column: &'a str,
+        alias: &'a str,
+    ) -> Self {
+        let clause = format!(
+            "join {} as {} on {} = {}",
+            qualify(db, table), quote(alias),
+            qualify(T::ALIAS, T::PRIMARY_KEY), qualify(alias, column),
         );
-        return self;
+        self.columns.push((alias.to_string(), column.to_string()));
+        return self.push_join(db, table, alias, clause);
+    }
+    pub fn join<U>(self) -> Self
+    where
+        U: ForeignKey<T>
+    {
+        return self.join_column(U::DB, U::TABLE, U::FOREIGN_KEY, U::ALIAS);
+    }
+    pub fn join_as<'a, U>(self, alias: &'a str) -> Self
+    where
+        U: ForeignKey<T>
+    {
+        return self.join_column(U::DB, U::TABLE, U::FOREIGN_KEY, alias);
+    }
+    pub fn join_by<'a, U>(self, column: &'a str) -> Self
+    where
+        U: PrimaryKeyModel,
+    {
+        return self.join_column(U::DB, U::TABLE, column, U::ALIAS);
+    }
+    pub fn join_by_as<'a, U>(self, column: &'a str, alias: &'a str) -> Self
+    where
+        U: PrimaryKeyModel,
+    {
+        return self.join_column(U::DB, U::TABLE, column, alias);
     }
     pub fn join_and(mut self) -> Self {
         match self.query_type {
@@ -253,17 +477,16 @@ impl<'query, T> Query<'query, T> where T: PrimaryKeyModel {
                 join_str, dlim
             )
         );
+        self.join_conj = true;
         return self;
     }
-    fn filter_join<'a, U>(
+    fn filter_join<'a>(
         mut self,
         op: &'a str,
+        alias: &'a str,
         column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
-    where
-        U: ForeignKey<T>
-    {
+        value: impl Into<Param<'query>>
+    ) -> Self {
         match self.query_type {
             QueryType::Select => {},
             QueryType::Update => panic!("Update-From is not yet supported"),
@@ -272,360 +495,1146 @@ impl<'query, T> Query<'query, T> where T: PrimaryKeyModel {
         let dlim;
         if self.join.is_none() {
             panic!("Cannot add another join constraint when there is no join");
-        } else {
+        } else if self.join_conj {
             join_str = self.join.unwrap();
             dlim = String::from(" ");
+        } else {
+            join_str = self.join.unwrap();
+            dlim = String::from(" and ");
         }
+        self.join_conj = false;
         let param_num = self.select_params.len() + self.update_params.len();
         let param_name = format!(":param{}", param_num);
-        self.select_params.insert(param_name.clone(), Box::new(value));
+        self.select_params.insert(param_name.clone(), value.into());
+        self.param_columns.insert(param_name.clone(), (alias.to_string(), column.to_string()));
+        self.columns.push((alias.to_string(), column.to_string()));
         self.join = Some(
             format!(
-                "{}{}{}.{} {} {}",
+                "{}{}{} {} {}",
                 join_str, dlim,
-                U::ALIAS, column,
+                qualify(alias, column),
                 op, param_name,
             )
         );
         return self;
     }
-    pub fn join_eq<'a, U>(
+    pub fn join_fk_eq<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>("=", column, value);
+        return self.filter_join("=", U::ALIAS, column, value);
     }
-    pub fn join_ne<'a, U>(
+    pub fn join_fk_ne<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>("!=", column, value);
+        return self.filter_join("!=", U::ALIAS, column, value);
     }
-    pub fn join_gt<'a, U>(
+    pub fn join_fk_gt<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>(">", column, value);
+        return self.filter_join(">", U::ALIAS, column, value);
     }
-    pub fn join_lt<'a, U>(
+    pub fn join_fk_lt<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>("<", column, value);
+        return self.filter_join("<", U::ALIAS, column, value);
     }
-    pub fn join_ge<'a, U>(
+    pub fn join_fk_ge<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>(">=", column, value);
+        return self.filter_join(">=", U::ALIAS, column, value);
     }
-    pub fn join_le<'a, U>(
+    pub fn join_fk_le<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: ForeignKey<T>
+        U: PrimaryKeyModel,
+        T: ForeignKey<U>
     {
-        return self.filter_join::<U>("<=", column, value);
+        return self.filter_join("<=", U::ALIAS, column, value);
     }
-    fn filter<'a, U>(
-        mut self,
-        op: &'a str,
+    pub fn join_eq<'a, U>(
+        self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        let clause_str;
-        let dlim;
-        if self.clause.is_none() {
-            clause_str = String::new();
-            dlim = String::from("where ");
-        } else {
-            clause_str = self.clause.unwrap();
-            dlim = String::from(" ");
-        }
-        let param_num = self.select_params.len() + self.update_params.len();
-        let param_name = format!(":param{}", param_num);
-        self.select_params.insert(param_name.clone(), Box::new(value));
-        match self.query_type {
-            QueryType::Select => {
-                self.clause = Some(
-                    format!(
-                        "{}{}{}.{} {} {}",
-                        clause_str, dlim,
-                        U::ALIAS, column,
-                        op, param_name,
-                    )
-                );
-            },
-            QueryType::Update => {
-                self.clause = Some(
-                    format!(
-                        "{}{}{} {} {}",
-                        clause_str, dlim,
-                        column,
-                        op, param_name,
-                    )
-                );
-            },
-        }
-        return self;
+        return self.filter_join("=", U::ALIAS, column, value);
     }
-    pub fn where_eq<'a, U>(
+    pub fn join_ne<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        return self.filter::<U>("=", column, value);
+        return self.filter_join("!=", U::ALIAS, column, value);
     }
-    pub fn where_ne<'a, U>(
+    pub fn join_gt<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        return self.filter::<U>("!=", column, value);
+        return self.filter_join(">", U::ALIAS, column, value);
     }
-    pub fn where_gt<'a, U>(
+    pub fn join_lt<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        return self.filter::<U>(">", column, value);
+        return self.filter_join("<", U::ALIAS, column, value);
     }
-    pub fn where_lt<'a, U>(
+    pub fn join_ge<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        return self.filter::<U>("<", column, value);
+        return self.filter_join(">=", U::ALIAS, column, value);
     }
-    pub fn where_ge<'a, U>(
+    pub fn join_le<'a, U>(
         self,
         column: &'a str,
-        value: &'query dyn ToSql
+        value: impl Into<Param<'query>>
     ) -> Self
     where
-        U: PrimaryKeyModel
+        U: ForeignKey<T>
     {
-        return self.filter::<U>(">=", column, value);
+        return self.filter_join("<=", U::ALIAS, column, value);
     }
-    pub fn where_le<'a, U>(
+    pub fn join_eq_as<'a>(
         self,
+        alias: &'a str,
         column: &'a str,
-        value: &'query dyn ToSql
-    ) -> Self
-    where
-        U: PrimaryKeyModel
-    {
-        return self.filter::<U>("<=", column, value);
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join("=", alias, column, value);
     }
-    fn concat<'a>(mut self, word: &'a str) -> Self {
-        let clause_str;
-        let dlim;
-        if self.clause.is_none() {
-            panic!("Cannot concatenate a clause when no clause exists");
-        } else {
-            clause_str = self.clause.unwrap();
-            dlim = String::from(" ");
-        }
-        self.clause = Some(
-            format!(
-                "{}{}{}",
-                clause_str, dlim, word
-            )
+    pub fn join_ne_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join("!=", alias, column, value);
+    }
+    pub fn join_gt_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join(">", alias, column, value);
+    }
+    pub fn join_lt_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join("<", alias, column, value);
+    }
+    pub fn join_ge_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join(">=", alias, column, value);
+    }
+    pub fn join_le_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter_join("<=", alias, column, value);
+    }
+    fn column_target<'a>(&mut self, alias: &'a str, column: &'a str) -> String {
+        self.columns.push((alias.to_string(), column.to_string()));
+        return match self.query_type {
+            QueryType::Select => qualify(alias, column),
+            QueryType::Update => quote(column),
+        };
+    }
+    fn filter<'a>(
+        mut self,
+        op: &'a str,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        let (clause_str, dlim) = self.next_clause();
+        let param_num = self.select_params.len() + self.update_params.len();
+        let param_name = format!(":param{}", param_num);
+        self.select_params.insert(param_name.clone(), value.into());
+        self.param_columns.insert(param_name.clone(), (alias.to_string(), column.to_string()));
+        let target = self.column_target(alias, column);
+        self.clause = Some(
+            format!(
+                "{}{}{} {} {}",
+                clause_str, dlim,
+                target,
+                op, param_name,
+            )
         );
         return self;
     }
-    pub fn and(self) -> Self {
-        return self.concat("and");
+    pub fn where_eq<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter("=", U::ALIAS, column, value);
     }
-    pub fn or(self) -> Self {
-        return self.concat("or");
+    pub fn where_ne<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter("!=", U::ALIAS, column, value);
     }
-    fn order_by<'a>(
+    pub fn where_gt<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter(">", U::ALIAS, column, value);
+    }
+    pub fn where_lt<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter("<", U::ALIAS, column, value);
+    }
+    pub fn where_ge<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter(">=", U::ALIAS, column, value);
+    }
+    pub fn where_le<'a, U>(
+        self,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter("<=", U::ALIAS, column, value);
+    }
+    pub fn where_eq_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter("=", alias, column, value);
+    }
+    pub fn where_ne_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter("!=", alias, column, value);
+    }
+    pub fn where_gt_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter(">", alias, column, value);
+    }
+    pub fn where_lt_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter("<", alias, column, value);
+    }
+    pub fn where_ge_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter(">=", alias, column, value);
+    }
+    pub fn where_le_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        value: impl Into<Param<'query>>
+    ) -> Self {
+        return self.filter("<=", alias, column, value);
+    }
+    fn filter_in<'a>(
         mut self,
-        dir: &'a str,
+        alias: &'a str,
         column: &'a str,
+        values: impl IntoIterator<Item = impl Into<Param<'query>>>
     ) -> Self {
-        let orderby_str;
-        let dlim;
-        if self.orderby.is_none() {
-            orderby_str = String::new();
-            dlim = String::from("order by ");
-        } else {
-            orderby_str = self.orderby.unwrap();
-            dlim = String::from(", ");
+        let (clause_str, dlim) = self.next_clause();
+        let mut param_names = Vec::new();
+        for value in values {
+            let param_num = self.select_params.len() + self.update_params.len();
+            let param_name = format!(":param{}", param_num);
+            self.select_params.insert(param_name.clone(), value.into());
+            self.param_columns.insert(param_name.clone(), (alias.to_string(), column.to_string()));
+            param_names.push(param_name);
         }
+        let target = self.column_target(alias, column);
+        self.clause = Some(
+            format!(
+                "{}{}{} in ({})",
+                clause_str, dlim,
+                target, param_names.join(", "),
+            )
+        );
+        return self;
+    }
+    pub fn where_in<'a, U>(
+        self,
+        column: &'a str,
+        values: impl IntoIterator<Item = impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.filter_in(U::ALIAS, column, values);
+    }
+    pub fn where_in_as<'a>(
+        self,
+        alias: &'a str,
+        column: &'a str,
+        values: impl IntoIterator<Item = impl Into<Param<'query>>>
+    ) -> Self {
+        return self.filter_in(alias, column, values);
+    }
+    pub fn when<F>(self, cond: bool, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self
+    {
+        return match cond {
+            true => f(self),
+            false => self.skip(),
+        };
+    }
+    fn skip(mut self) -> Self {
+        // a conjunction next to a filter that was left out is dropped with it
+        self.conj = None;
+        self.skipped = true;
+        return self;
+    }
+    pub fn when_some<V, F>(self, value: Option<V>, f: F) -> Self
+    where
+        F: FnOnce(Self, V) -> Self
+    {
+        return match value {
+            Some(value) => f(self, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_eq_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter("=", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_ne_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter("!=", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_gt_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter(">", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_lt_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter("<", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_ge_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter(">=", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    pub fn where_le_opt<'a, U>(
+        self,
+        column: &'a str,
+        value: Option<impl Into<Param<'query>>>
+    ) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return match value {
+            Some(value) => self.filter("<=", U::ALIAS, column, value),
+            None => self.skip(),
+        };
+    }
+    fn next_clause(&mut self) -> (String, String) {
+        self.skipped = false;
+        let conj = self.conj.take();
+        return match self.clause.take() {
+            None => (String::new(), String::new()),
+            Some(clause) => (clause, format!(" {} ", conj.unwrap_or("and"))),
+        };
+    }
+    fn concat(mut self, word: &'static str) -> Self {
+        if self.clause.is_none() && self.skipped {
+            return self;
+        }
+        if self.clause.is_none() {
+            panic!("Cannot concatenate a clause when no clause exists");
+        }
+        if self.conj.is_some() {
+            panic!("Cannot concatenate a clause twice");
+        }
+        self.conj = Some(word);
+        return self;
+    }
+    fn group_clause(mut self) -> Self {
+        if self.clause.is_none() {
+            panic!("Cannot group a clause when no clause exists");
+        }
+        self.clause = Some(format!("({})", self.clause.unwrap()));
+        return self;
+    }
+    pub fn and(self) -> Self {
+        return self.concat("and");
+    }
+    pub fn or(self) -> Self {
+        return self.concat("or");
+    }
+    pub fn select_as<'a>(mut self, alias: &'a str, column: &'a str, name: &'a str) -> Self {
         match self.query_type {
-            QueryType::Select => {
-                self.orderby = Some(
-                    format!(
-                        "{}{}{}.{} {}",
-                        orderby_str, dlim,
-                        T::ALIAS, column,
-                        dir,
-                    )
-                );
-            },
+            QueryType::Select => {},
             QueryType::Update => {
-                panic!("Update-From not yet supported");
+                panic!("Cannot select a column on an update query");
             },
         }
+        let target = self.column_target(alias, column);
+        self.select = format!("{}, {} as {}", self.select, target, quote(name));
         return self;
     }
+    pub fn select_on<'a, U>(self, column: &'a str, name: &'a str) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.select_as(U::ALIAS, column, name);
+    }
+    fn order_by_expr(mut self, dir: &'static str, expr: String) -> Self {
+        self.orderby.push(OrderTerm {
+            expr,
+            dir,
+            mods: OrderMods::default(),
+        });
+        return self;
+    }
+    fn order_by<'a>(
+        mut self,
+        dir: &'static str,
+        alias: &'a str,
+        column: &'a str,
+    ) -> Self {
+        let expr = self.column_target(alias, column);
+        return self.order_by_expr(dir, expr);
+    }
     pub fn orderby_asc<'a>(self, column: &'a str) -> Self {
-        return self.order_by("asc", column);
+        return self.order_by("asc", T::ALIAS, column);
     }
     pub fn orderby_desc<'a>(self, column: &'a str) -> Self {
-        return self.order_by("desc", column);
+        return self.order_by("desc", T::ALIAS, column);
+    }
+    pub fn orderby_asc_on<'a, U>(self, column: &'a str) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.order_by("asc", U::ALIAS, column);
+    }
+    pub fn orderby_desc_on<'a, U>(self, column: &'a str) -> Self
+    where
+        U: PrimaryKeyModel
+    {
+        return self.order_by("desc", U::ALIAS, column);
+    }
+    pub fn orderby_asc_as<'a>(self, alias: &'a str, column: &'a str) -> Self {
+        return self.order_by("asc", alias, column);
+    }
+    pub fn orderby_desc_as<'a>(self, alias: &'a str, column: &'a str) -> Self {
+        return self.order_by("desc", alias, column);
+    }
+    // the expression is written into the sql as given and must never carry user input
+    pub fn orderby_expr_asc_unchecked<'a>(self, expr: &'a str) -> Self {
+        return self.order_by_expr("asc", expr.to_string());
+    }
+    pub fn orderby_expr_desc_unchecked<'a>(self, expr: &'a str) -> Self {
+        return self.order_by_expr("desc", expr.to_string());
+    }
+    fn order_mods(&mut self) -> &mut OrderMods {
+        // modifiers always apply to the order by given before them
+        return match self.orderby.last_mut() {
+            Some(term) => &mut term.mods,
+            None => panic!("Cannot modify an order by when no order by exists"),
+        };
+    }
+    pub fn nulls_first(mut self) -> Self {
+        self.order_mods().nulls = Some("nulls first");
+        return self;
+    }
+    pub fn nulls_last(mut self) -> Self {
+        self.order_mods().nulls = Some("nulls last");
+        return self;
+    }
+    pub fn collate<'a>(mut self, collation: &'a str) -> Self {
+        self.order_mods().collate = Some(collation.to_string());
+        return self;
+    }
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        return self;
+    }
+    fn active_filters(&self) -> Vec<String> {
+        // every alias of an active model is filtered, whenever it was joined
+        let mut filters = Vec::new();
+        if self.include_inactive {
+            return filters;
+        }
+        for (alias, db, table) in &self.tables {
+            for (active_db, active_table, column) in &self.active {
+                if active_db != db || active_table != table {
+                    continue;
+                }
+                filters.push(match self.query_type {
+                    QueryType::Select => format!("{} = 1", qualify(alias, column)),
+                    QueryType::Update => format!("{} = 1", quote(column)),
+                });
+            }
+        }
+        return filters;
+    }
+    fn filter_to_string(&self) -> String {
+        let mut sql = String::new();
+        let active = self.active_filters();
+        if !active.is_empty() {
+            let active = active.join(" and ");
+            match self.clause.clone() {
+                Some(clause) => sql.push_str(&format!(" where {} and ({})", active, clause)),
+                None => sql.push_str(&format!(" where {}", active)),
+            }
+        } else if self.clause.is_some() {
+            let clause = self.clause.clone().unwrap();
+            sql.push_str(&format!(" where {}", clause));
+        }
+        return sql;
+    }
+    fn order_to_string(&self) -> String {
+        let mut sql = String::new();
+        if !self.orderby.is_empty() {
+            let orderby = self.orderby.iter()
+                .map(|term| term.to_sql())
+                .collect::<Vec<String>>()
+                .join(", ");
+            sql.push_str(&format!(" order by {}", orderby));
+        }
+        if self.limit.is_some() {
+            sql.push_str(&format!(" limit {}", self.limit.unwrap()));
+        }
+        return sql;
     }
     pub fn query_to_string(&self) -> String {
-        let mut sql;
+        if self.conj.is_some() {
+            panic!("Cannot end a clause with a conjunction");
+        }
+        let filter = self.filter_to_string();
+        let order = self.order_to_string();
         match self.query_type {
             QueryType::Select => {
-                sql = format!("{} {}", self.select, self.from);
+                let mut sql = format!("{} {}", self.select, self.from);
                 if self.join.is_some() {
                     let join = self.join.clone().unwrap();
                     sql.push_str(&format!(" {}", join));
                 }
+                return format!("{}{}{}", sql, filter, order);
             },
             QueryType::Update => {
                 if self.set.is_none() {
                     panic!("Cannot create an update statement without any set values");
                 }
-                sql = format!("{} {}", self.update, self.set.clone().unwrap());
+                let sql = format!("{} {}", self.update, self.set.clone().unwrap());
+                if self.limit.is_none() {
+                    if !self.orderby.is_empty() {
+                        panic!("Cannot order an update statement without a limit");
+                    }
+                    return format!("{}{}", sql, filter);
+                }
+                // update-limit needs SQLITE_ENABLE_UPDATE_DELETE_LIMIT, so limit the keys instead
+                let key = quote(T::PRIMARY_KEY);
+                return format!(
+                    "{} where {} in (select {} from {}{}{})",
+                    sql, key, key, qualify(T::DB, T::TABLE), filter, order
+                );
             },
         }
-        if self.clause.is_some() {
-            let clause = self.clause.clone().unwrap();
-            sql.push_str(&format!(" {}", clause));
+    }
+    pub fn to_debug_sql(&self) -> String {
+        let (sql, value_order, sensitive) = self.bind();
+        let literals = self.observer.render_params(&value_order, &sensitive);
+        return inline_params(&sql, literals);
+    }
+    fn is_sensitive(&self, key: &String) -> bool {
+        let (alias, column) = match self.param_columns.get(key) {
+            Some(target) => target,
+            None => return false,
+        };
+        let marked = self.sensitive.iter()
+            .any(|(sensitive_alias, sensitive_column)| {
+                return sensitive_alias.eq_ignore_ascii_case(alias)
+                    && sensitive_column.eq_ignore_ascii_case(column);
+            });
+        if marked {
+            return true;
         }
-        if self.orderby.is_some() {
-            let orderby = self.orderby.clone().unwrap();
-            sql.push_str(&format!(" {}", orderby));
+        // columns marked by model apply to every alias that model was joined under
+        return self.tables.iter()
+            .filter(|(table_alias, _, _)| table_alias == alias)
+            .any(|(_, db, table)| {
+                let marked = self.sensitive_tables.iter()
+                    .any(|(sensitive_db, sensitive_table, sensitive_column)| {
+                        return sensitive_db == db && sensitive_table == table
+                            && sensitive_column.eq_ignore_ascii_case(column);
+                    });
+                return marked || is_registered_sensitive(db, table, column);
+            });
+    }
+    fn bind(&self) -> (String, Vec<&Param<'query>>, Vec<bool>) {
+        return self.bind_sql(self.query_to_string());
+    }
+    fn bind_sql(&self, sql: String) -> (String, Vec<&Param<'query>>, Vec<bool>) {
+        let (bound, keys) = self.bind_keys(sql);
+        let value_order = keys.iter()
+            .map(|key| self.param(key))
+            .collect();
+        let sensitive = keys.iter()
+            .map(|key| self.is_sensitive(key))
+            .collect();
+        return (bound, value_order, sensitive);
+    }
+    fn param(&self, key: &String) -> &Param<'query> {
+        return match self.select_params.get(key) {
+            Some(value) => value,
+            None => &self.update_params[key],
+        };
+    }
+    fn bind_keys(&self, sql: String) -> (String, Vec<String>) {
+        // single pass over the original sql so that parameters bind in the order they appear
+        let mut bound = String::with_capacity(sql.len());
+        let mut keys = Vec::new();
+        let mut quote: Option<char> = None;
+        let mut chars = sql.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (None, ':') => {
+                    let mut key = String::from(":");
+                    while let Some(&next) = chars.peek() {
+                        if !next.is_ascii_alphanumeric() && next != '_' {
+                            break;
+                        }
+                        key.push(next);
+                        chars.next();
+                    }
+                    if self.select_params.contains_key(&key) || self.update_params.contains_key(&key) {
+                        bound.push('?');
+                        keys.push(key);
+                    } else {
+                        bound.push_str(&key);
+                    }
+                    continue;
+                },
+                (None, '\'') | (None, '"') | (None, '`') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                _ => {},
+            }
+            bound.push(c);
         }
-        return sql;
+        return (bound, keys);
+    }
+    fn resolve_columns(&self) -> Vec<(String, String, String)> {
+        return self.columns.iter()
+            .map(|(alias, column)| {
+                let table = match self.query_type {
+                    QueryType::Select => self.tables.iter()
+                        .find(|(table_alias, _, _)| table_alias == alias),
+                    QueryType::Update => self.tables.first(),
+                };
+                return match table {
+                    Some((_, schema, table)) => (schema.clone(), table.clone(), column.clone()),
+                    None => (String::new(), alias.clone(), column.clone()),
+                };
+            })
+            .collect();
+    }
+    fn preflight<'a>(
+        &self,
+        db: &mut impl DbCtx,
+        sql: &'a str,
+        value_order: &[&Param]
+    ) -> Result<(), BuildliteError> {
+        ident::validate_columns(db, &self.resolve_columns())?;
+        return match self.scan_guard {
+            Some(max_rows) => {
+                let query_plan = plan::explain(db, sql, value_order)?;
+                plan::check_scans(db, &query_plan, &self.tables, max_rows)
+            },
+            None => Ok(()),
+        };
+    }
+    pub fn explain(&self, db: &mut impl DbCtx) -> Result<QueryPlan, BuildliteError> {
+        let (sql, value_order, sensitive) = self.bind();
+        return plan::explain(db, &sql, &value_order).map_err(|e| {
+            return e.with_query(&sql, self.observer.render_params(&value_order, &sensitive));
+        });
+    }
+    fn update_guard(&self) -> UpdateGuard {
+        let mut guard = self.guard;
+        guard.filtered = self.clause.is_some();
+        return guard;
+    }
+    pub fn compile(self) -> CompiledQuery<'query, T> {
+        let guard = self.update_guard();
+        let columns = self.resolve_columns();
+        let (sql, keys) = self.bind_keys(self.query_to_string());
+        let params = keys.iter()
+            .map(|key| self.param(key).clone())
+            .collect::<Vec<Param<'query>>>();
+        let sensitive = keys.iter()
+            .map(|key| self.is_sensitive(key))
+            .collect::<Vec<bool>>();
+        // slots follow the order parameters were added to the builder, not the sql
+        let mut slots = vec![ Vec::new(); self.select_params.len() + self.update_params.len() ];
+        for (position, key) in keys.iter().enumerate() {
+            let slot = key.trim_start_matches(":param").parse::<usize>().unwrap();
+            slots[slot].push(position);
+        }
+        return CompiledQuery::new(
+            self.query_type,
+            sql,
+            params,
+            slots,
+            sensitive,
+            self.retry,
+            self.interrupt,
+            self.observer,
+            guard,
+            columns,
+            self.tables,
+            self.scan_guard
+        );
     }
     pub fn execute_update(self, db: &mut impl DbCtx) -> Result<usize, BuildliteError> {
-        let mut sql = self.query_to_string();
-        // get query order of parameters
-        let keys;
-        let select_keys = self.select_params.keys();
         match self.query_type {
             QueryType::Select => {
                 panic!("Cannot execute an update from a select query");
-            }
-            QueryType::Update => {
-                let update_keys = self.update_params.keys();
-                keys = select_keys.chain(update_keys);
             },
+            QueryType::Update => {},
         }
-        let mut key_indices: Vec<(usize, String)> = Vec::new();
-        for key in keys {
-            let index = sql.find(key).unwrap();
-            sql = sql.replace(key, "?");
-            key_indices.push((index, key.clone()));
-        }
-        key_indices.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-        let mut value_order = Vec::new();
-        for key_index in key_indices {
-            let key = key_index.1;
-            let value = match self.select_params.get(&key) {
-                Some(value) => value,
-                None => self.update_params.get(&key).unwrap(),
-            };
-            value_order.push(value);
-        }
-        let param = worm::core::sql::params_from_iter(value_order);
-        let c = db.use_connection();
+        let (sql, value_order, sensitive) = self.bind();
+        let guard = self.update_guard();
+        return guard.check_filtered()
+            .and_then(|_| match guard.expect_rows {
+                Some(_) => in_savepoint(db, "buildlite_expect", |tx| {
+                    let affected = self.run_update(tx, &sql, &value_order, &sensitive)?;
+                    return guard.check_affected(affected);
+                }),
+                None => self.run_update(db, &sql, &value_order, &sensitive),
+            })
+            .map_err(|e| {
+                return e.with_query(&sql, self.observer.render_params(&value_order, &sensitive));
+            });
+    }
+    fn run_update<'a>(
+        &self,
+        db: &mut impl DbCtx,
+        sql: &'a str,
+        value_order: &[&Param],
+        sensitive: &[bool]
+    ) -> Result<usize, BuildliteError> {
+        let count = |n: &usize| Some(*n);
+        return self.observer.run(sql, value_order, sensitive, count, || {
+            self.preflight(db, sql, value_order)?;
+            return self.interrupt.run(db, |db, watch| self.retry.run_watched(watch, || {
+                let param = worm::core::sql::params_from_iter(value_order.iter());
+                let c = db.use_connection();
+                return Ok(c.execute(sql, param).quick_match()?);
+            }));
+        });
+    }
+    pub fn execute_map<U, F>(
+        self,
+        db: &mut impl DbCtx,
+        mut map: F
+    ) -> Result<Vec<U>, BuildliteError>
+    where
+        F: FnMut(&Row) -> Result<U, RusqliteError>
+    {
         match self.query_type {
-            QueryType::Select => {
-                panic!("Cannot execute an update on a select query");
-            },
+            QueryType::Select => {},
             QueryType::Update => {
-                return Ok(c.execute(&sql, param).quick_match()?);
+                panic!("Cannot execute a select on an update query");
             },
         }
+        let (sql, value_order, sensitive) = self.bind();
+        let count = |objs: &Vec<U>| Some(objs.len());
+        return self.observer.run(&sql, &value_order, &sensitive, count, || {
+            self.preflight(db, &sql, &value_order)?;
+            return self.interrupt.run(db, |db, watch| self.retry.run_watched(watch, || {
+                let param = worm::core::sql::params_from_iter(value_order.iter());
+                let c = db.use_connection();
+                let mut objs = Vec::new();
+                let mut stmt = c.prepare(&sql).quick_match()?;
+                let mut rows = stmt.query(param).quick_match()?;
+                while let Some(row) = rows.next().quick_match()? {
+                    objs.push(map(row).quick_match()?);
+                }
+                return Ok(objs);
+            }));
+        }).map_err(|e| {
+            return e.with_query(&sql, self.observer.render_params(&value_order, &sensitive));
+        });
     }
     pub fn execute(self, db: &mut impl DbCtx) -> Result<Vec<T>, BuildliteError> {
-        let mut sql = self.query_to_string();
-        // get query order of parameters
-        let keys;
-        let select_keys = self.select_params.keys();
+        return self.execute_map(db, |row| T::from_row(row));
+    }
+    pub fn execute_iter<F, R>(
+        self,
+        db: &mut impl DbCtx,
+        f: F
+    ) -> Result<R, BuildliteError>
+    where
+        F: FnOnce(RowIter<T>) -> R
+    {
         match self.query_type {
-            QueryType::Select => {
-                keys = select_keys;
-            }
+            QueryType::Select => {},
             QueryType::Update => {
                 panic!("Cannot execute a select on an update query");
             },
         }
-        let mut key_indices: Vec<(usize, String)> = Vec::new();
-        for key in keys {
-            let index = sql.find(key).unwrap();
-            sql = sql.replace(key, "?");
-            key_indices.push((index, key.clone()));
-        }
-        key_indices.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-        let mut value_order = Vec::new();
-        for key_index in key_indices {
-            let key = key_index.1;
-            let value = match self.select_params.get(&key) {
-                Some(value) => value,
-                None => self.update_params.get(&key).unwrap(),
-            };
-            value_order.push(value);
-        }
-        let param = worm::core::sql::params_from_iter(value_order);
-        let c = db.use_connection();
-        let mut objs = Vec::new();
-        match self.query_type {
-            QueryType::Select => {
+        let (sql, value_order, sensitive) = self.bind();
+        let count = |_: &R| None;
+        return self.observer.run(&sql, &value_order, &sensitive, count, || {
+            self.preflight(db, &sql, &value_order)?;
+            return self.interrupt.run(db, |db, watch| {
+                let param = worm::core::sql::params_from_iter(value_order.iter());
+                let c = db.use_connection();
                 let mut stmt = c.prepare(&sql).quick_match()?;
-                let mut rows = stmt.query(param).quick_match()?;
-                while let Some(row) = rows.next().quick_match()? {
-                    objs.push(T::from_row(row).quick_match()?);
+                let rows = stmt.query(param).quick_match()?;
+                return Ok(f(RowIter::new(rows, watch.clone())));
+            });
+        }).map_err(|e| {
+            return e.with_query(&sql, self.observer.render_params(&value_order, &sensitive));
+        });
+    }
+    pub fn for_each_row<F>(
+        self,
+        db: &mut impl DbCtx,
+        mut f: F
+    ) -> Result<(), BuildliteError>
+    where
+        F: FnMut(T) -> bool
+    {
+        return self.execute_iter(db, |rows| {
+            for row in rows {
+                if !f(row?) {
+                    break;
                 }
-            },
+            }
+            return Ok(());
+        })?;
+    }
+    fn execute_keyed(
+        self,
+        db: &mut impl DbCtx
+    ) -> Result<Vec<(i64, T)>, BuildliteError> {
+        return self.execute_map(db, |row| {
+            let obj = T::from_row(row)?;
+            return Ok((row.get("buildlite_key")?, obj));
+        });
+    }
+    fn inherit<C>(&self, mut child: Query<'query, C>) -> Query<'query, C> {
+        // related rows load under the same settings as the query they belong to
+        child.active = self.active.clone();
+        child.include_inactive = self.include_inactive;
+        child.retry = self.retry;
+        child.interrupt = self.interrupt.clone();
+        child.observer = self.observer.clone();
+        child.sensitive_tables = self.sensitive_tables.clone();
+        child.scan_guard = self.scan_guard;
+        return child;
+    }
+    pub fn execute_through<J, U>(
+        self,
+        db: &mut impl DbCtx
+    ) -> Result<Vec<(T, Vec<U>)>, BuildliteError>
+    where
+        T: PrimaryKey,
+        U: PrimaryKeyModel,
+        J: PrimaryKeyModel + ForeignKey<T> + ForeignKey<U>,
+    {
+        let targets = self.inherit(Query::<U>::select())
+            .join_by::<J>(<J as ForeignKey<U>>::FOREIGN_KEY)
+            .select_on::<J>(<J as ForeignKey<T>>::FOREIGN_KEY, "buildlite_key");
+        let parents = self.execute(db)?;
+        let ids = parents.iter()
+            .map(|parent| parent.get_id())
+            .collect::<Vec<i64>>();
+        let mut grouped: HashMap<i64, Vec<U>> = HashMap::new();
+        for chunk in ids.chunks(MAX_IN_PARAMS) {
+            let chunk_targets = targets.clone()
+                .where_in::<J>(<J as ForeignKey<T>>::FOREIGN_KEY, chunk.iter().map(|id| Param::owned(*id)))
+                .execute_keyed(db)?;
+            for (id, target) in chunk_targets {
+                grouped.entry(id).or_insert_with(Vec::new).push(target);
+            }
+        }
+        let mut objs = Vec::new();
+        for parent in parents {
+            let children = grouped.remove(&parent.get_id()).unwrap_or_default();
+            objs.push((parent, children));
+        }
+        return Ok(objs);
+    }
+    pub fn execute_grouped<P>(
+        self,
+        db: &mut impl DbCtx,
+        parents: &[P]
+    ) -> Result<HashMap<i64, Vec<T>>, BuildliteError>
+    where
+        P: PrimaryKeyModel + PrimaryKey,
+        T: ForeignKey<P>,
+    {
+        let ids = parents.iter()
+            .map(|parent| parent.get_id())
+            .collect::<Vec<i64>>();
+        let query = match self.clause.is_some() {
+            true => self.group_clause().and(),
+            false => self,
+        };
+        let mut grouped: HashMap<i64, Vec<T>> = HashMap::new();
+        for chunk in ids.chunks(MAX_IN_PARAMS) {
+            let children = query.clone()
+                .where_in::<T>(T::FOREIGN_KEY, chunk.iter().map(|id| Param::owned(*id)))
+                .execute(db)?;
+            for child in children {
+                grouped.entry(child.get_fk_value()).or_insert_with(Vec::new).push(child);
+            }
+        }
+        return Ok(grouped);
+    }
+    pub fn execute_with<C, F>(
+        self,
+        db: &mut impl DbCtx,
+        children: F
+    ) -> Result<Vec<(T, Vec<C>)>, BuildliteError>
+    where
+        T: PrimaryKey,
+        C: PrimaryKeyModel + ForeignKey<T>,
+        F: FnOnce(Query<'query, C>) -> Query<'query, C>,
+    {
+        let children = children(self.inherit(Query::<C>::select()));
+        let parents = self.execute(db)?;
+        let mut grouped = children.execute_grouped(db, &parents)?;
+        let mut objs = Vec::new();
+        for parent in parents {
+            let children = grouped.remove(&parent.get_id()).unwrap_or_default();
+            objs.push((parent, children));
+        }
+        return Ok(objs);
+    }
+    fn execute_wrapped<'a, U>(
+        mut self,
+        db: &mut impl DbCtx,
+        wrap: &'a str
+    ) -> Result<U, BuildliteError>
+    where
+        U: FromSql
+    {
+        match self.query_type {
+            QueryType::Select => {},
             QueryType::Update => {
                 panic!("Cannot execute a select on an update query");
             },
         }
+        self.select = String::from("select 1");
+        if self.limit.is_none() {
+            self.orderby.clear();
+        }
+        let inner = self.query_to_string();
+        let (sql, value_order, sensitive) = self.bind_sql(format!("{}({})", wrap, inner));
+        let count = |_: &U| Some(1);
+        return self.observer.run(&sql, &value_order, &sensitive, count, || {
+            self.preflight(db, &sql, &value_order)?;
+            return self.interrupt.run(db, |db, watch| self.retry.run_watched(watch, || {
+                let param = worm::core::sql::params_from_iter(value_order.iter());
+                let c = db.use_connection();
+                return Ok(c.query_row(&sql, param, |row| row.get(0)).quick_match()?);
+            }));
+        }).map_err(|e| {
+            return e.with_query(&sql, self.observer.render_params(&value_order, &sensitive));
+        });
+    }
+    pub fn execute_exists(self, db: &mut impl DbCtx) -> Result<bool, BuildliteError> {
+        return self.execute_wrapped(db, "select exists");
+    }
+    pub fn execute_count(self, db: &mut impl DbCtx) -> Result<i64, BuildliteError> {
+        return self.execute_wrapped(db, "select count(*) from ");
+    }
+    fn execute_limited(self, db: &mut impl DbCtx, max: usize) -> Result<Vec<T>, BuildliteError> {
+        let limit = match self.limit {
+            Some(limit) if limit < max => limit,
+            _ => max,
+        };
+        return self.limit(limit).execute(db);
+    }
+    pub fn execute_optional(self, db: &mut impl DbCtx) -> Result<Option<T>, BuildliteError> {
+        return Ok(self.execute_limited(db, 1)?.pop());
+    }
+    pub fn execute_one(self, db: &mut impl DbCtx) -> Result<T, BuildliteError> {
+        let mut res = self.execute_limited(db, 2)?;
+        if res.len() > 1 {
+            return Err(BuildliteError::TooManyRowsError);
+        }
+        return match res.pop() {
+            Some(val) => Ok(val),
+            None => Err(BuildliteError::NoRowsError),
+        };
+    }
+    pub fn find_by_pk(db: &mut impl DbCtx, id: i64) -> Result<T, BuildliteError> {
+        return Query::<T>::select()
+            .where_eq::<T>(T::PRIMARY_KEY, Param::owned(id))
+            .execute_one(db);
+    }
+    pub fn find_by_unique_name<'a>(
+        db: &mut impl DbCtx,
+        name: &'a str
+    ) -> Result<T, BuildliteError>
+    where
+        T: UniqueNameModel,
+    {
+        return Query::<T>::select()
+            .where_eq::<T>(<T as UniqueNameModel>::NAME, name)
+            .execute_one(db);
+    }
+    pub fn find_many_by_pk(db: &mut impl DbCtx, ids: &[i64]) -> Result<Vec<T>, BuildliteError> {
+        let mut objs = Vec::new();
+        for chunk in ids.chunks(MAX_IN_PARAMS) {
+            let chunk_objs = Query::<T>::select()
+                .where_in::<T>(T::PRIMARY_KEY, chunk)
+                .execute(db)?;
+            objs.extend(chunk_objs);
+        }
         return Ok(objs);
     }
     pub fn execute_row(self, db: &mut impl DbCtx) -> Result<T, BuildliteError> {
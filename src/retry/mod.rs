@@ -0,0 +1,116 @@
+use {
+    crate::{
+        error::BuildliteError,
+        interrupt::Watch,
+        transaction::{
+            in_transaction,
+            Transaction,
+        },
+    },
+    std::{
+        thread,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    worm::core::DbCtx,
+};
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+    deadline: Option<Duration>,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy::new(1);
+    }
+}
+impl RetryPolicy {
+    pub fn new(max_attempts: usize) -> Self {
+        return RetryPolicy {
+            max_attempts,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            deadline: None,
+        };
+    }
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        return self;
+    }
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        return self;
+    }
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        return self;
+    }
+    fn is_busy(e: &BuildliteError) -> bool {
+        return match e {
+            BuildliteError::BusyError(..) => true,
+            _ => false,
+        };
+    }
+    pub(crate) fn run<F, R>(&self, f: F) -> Result<R, BuildliteError>
+    where
+        F: FnMut() -> Result<R, BuildliteError>
+    {
+        return self.run_watched(&Watch::default(), f);
+    }
+    pub(crate) fn run_watched<F, R>(&self, watch: &Watch, mut f: F) -> Result<R, BuildliteError>
+    where
+        F: FnMut() -> Result<R, BuildliteError>
+    {
+        let start = Instant::now();
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            watch.check()?;
+            let e = match f() {
+                Ok(val) => return Ok(val),
+                Err(e) if Self::is_busy(&e) => e,
+                Err(e) => return Err(e),
+            };
+            let out_of_time = match self.deadline {
+                Some(deadline) => start.elapsed() + backoff > deadline,
+                None => false,
+            };
+            if attempt >= self.max_attempts || out_of_time {
+                return Err(e);
+            }
+            // never wait past the query timeout, and give up early once cancelled
+            let mut waited = Duration::ZERO;
+            while waited < backoff {
+                let step = match watch.remaining() {
+                    Some(remaining) => std::cmp::min(CANCEL_POLL, remaining),
+                    None => CANCEL_POLL,
+                };
+                let step = std::cmp::min(step, backoff - waited);
+                if step.is_zero() {
+                    break;
+                }
+                thread::sleep(step);
+                waited += step;
+                watch.check()?;
+            }
+            backoff = std::cmp::min(backoff * 2, self.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+pub fn in_transaction_retry<D, F, R>(
+    db: &mut D,
+    policy: &RetryPolicy,
+    mut f: F
+) -> Result<R, BuildliteError>
+where
+    D: DbCtx,
+    F: FnMut(&mut Transaction<D>) -> Result<R, BuildliteError>,
+{
+    return policy.run(|| in_transaction(db, &mut f));
+}
@@ -0,0 +1,38 @@
+use {
+    crate::{
+        error::{
+            BuildliteError,
+            BuildliteErrorMatch,
+        },
+        interrupt::Watch,
+    },
+    worm::core::{
+        PrimaryKeyModel,
+        sql::Rows,
+    },
+};
+pub struct RowIter<'stmt, T> {
+    rows: Rows<'stmt>,
+    watch: Watch,
+    _value: Option<T>,
+}
+impl<'stmt, T> RowIter<'stmt, T> where T: PrimaryKeyModel {
+    pub(crate) fn new(rows: Rows<'stmt>, watch: Watch) -> Self {
+        return RowIter {
+            rows,
+            watch,
+            _value: None,
+        };
+    }
+}
+impl<'stmt, T> Iterator for RowIter<'stmt, T> where T: PrimaryKeyModel {
+    type Item = Result<T, BuildliteError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        return match self.rows.next().quick_match() {
+            Ok(Some(row)) => Some(T::from_row(row).quick_match()),
+            Ok(None) => None,
+            // rows are fetched inside the caller's closure, after the runner has returned
+            Err(e) => Some(Err(self.watch.classify(e))),
+        };
+    }
+}
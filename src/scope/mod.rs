@@ -0,0 +1,16 @@
+use {
+    crate::Query,
+    worm::core::PrimaryKeyModel,
+};
+pub trait Scope<'query, T> where T: PrimaryKeyModel {
+    fn apply(self, query: Query<'query, T>) -> Query<'query, T>;
+}
+impl<'query, T, F> Scope<'query, T> for F
+where
+    T: PrimaryKeyModel,
+    F: FnOnce(Query<'query, T>) -> Query<'query, T>,
+{
+    fn apply(self, query: Query<'query, T>) -> Query<'query, T> {
+        return self(query);
+    }
+}
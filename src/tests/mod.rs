@@ -1,6 +1,14 @@
 mod query_builder {
     use {
-        crate::Query,
+        crate::{
+            BuildliteError,
+            Param,
+            Query,
+            Relations,
+            Scope,
+            SensitiveColumns,
+            register_sensitive_columns,
+        },
         worm::derive::Worm,
     };
     #[derive(Worm)]
@@ -25,13 +33,46 @@ mod query_builder {
         #[dbcolumn(column(name="Active", active_flag, insertable))]
         active: bool,
     }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="TestDb", name="ThirdTable", alias="thirdtable"))]
+    struct ThirdTable {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Another_Id", foreign_key="AnotherTable"))]
+        another_id: i64,
+        #[dbcolumn(column(name="Name", unique_name, insertable))]
+        name: String,
+    }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="TestDb", name="FourthTable", alias="fourthtable"))]
+    struct FourthTable {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Test_Id", foreign_key="TestTable"))]
+        test_id: i64,
+        #[dbcolumn(column(name="Third_Id", foreign_key="ThirdTable"))]
+        third_id: i64,
+    }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="TestDb", name="SecretTable", alias="secrettable"))]
+    struct SecretTable {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Test_Id", foreign_key="TestTable"))]
+        test_id: i64,
+        #[dbcolumn(column(name="Token", insertable))]
+        token: String,
+    }
+    impl SensitiveColumns for SecretTable {
+        const SENSITIVE: &'static [&'static str] = &[ SecretTable::TOKEN ];
+    }
     #[test]
     fn test_select() {
         let q = Query::<TestTable>::select()
             .where_eq::<TestTable>(TestTable::ID, &1).and()
             .where_gt::<TestTable>(TestTable::ACTIVE, &0);
         let test_against = format!(
-            "select testtable.* from TestDb.TestTable as testtable where testtable.Id = :param0 and testtable.Active > :param1"
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Id" = :param0 and "testtable"."Active" > :param1"#
         );
         assert_eq!(q.query_to_string(), test_against);
     }
@@ -42,7 +83,7 @@ mod query_builder {
             .join_eq::<AnotherTable>(AnotherTable::ACTIVE, &1)
             .where_eq::<TestTable>(TestTable::ID, &1);
         let test_against = format!(
-            "select testtable.* from TestDb.TestTable as testtable join TestDb.AnotherTable as anothertable on testtable.Id = anothertable.Test_Id and anothertable.Active = :param0 where testtable.Id = :param1"
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" join "TestDb"."AnotherTable" as "anothertable" on "testtable"."Id" = "anothertable"."Test_Id" and "anothertable"."Active" = :param0 where "testtable"."Id" = :param1"#
         );
         assert_eq!(q.query_to_string(), test_against);
     }
@@ -53,7 +94,7 @@ mod query_builder {
             .join_fk_eq::<TestTable>(TestTable::ID, &1)
             .where_eq::<AnotherTable>(AnotherTable::ACTIVE, &1);
         let test_against = format!(
-            "select anothertable.* from TestDb.AnotherTable as anothertable join TestDb.TestTable as testtable on anothertable.Test_Id = testtable.Id and testtable.Id = :param0 where anothertable.Active = :param1"
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" and "testtable"."Id" = :param0 where "anothertable"."Active" = :param1"#
         );
         assert_eq!(q.query_to_string(), test_against);
     }
@@ -63,23 +104,349 @@ mod query_builder {
             .orderby_desc(AnotherTable::NAME)
             .where_eq::<AnotherTable>(AnotherTable::ACTIVE, &1);
         let test_against = format!(
-            "select anothertable.* from TestDb.AnotherTable as anothertable where anothertable.Active = :param0 order by anothertable.Name desc"
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" where "anothertable"."Active" = :param0 order by "anothertable"."Name" desc"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_join_alias() {
+        let q = Query::<AnotherTable>::select()
+            .join_fk_as::<TestTable>("parent").join_and()
+            .join_eq_as("parent", TestTable::ACTIVE, &1)
+            .join_fk_as::<TestTable>("other")
+            .where_ne_as("other", TestTable::NAME, &"Hello")
+            .orderby_asc_as("parent", TestTable::NAME);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id" and "parent"."Active" = :param0 join "TestDb"."TestTable" as "other" on "anothertable"."Test_Id" = "other"."Id" where "other"."Name" != :param1 order by "parent"."Name" asc"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_select_alias() {
+        let q = Query::<AnotherTable>::select()
+            .join_fk_as::<TestTable>("parent")
+            .select_as("parent", TestTable::NAME, "parent_name")
+            .select_on::<AnotherTable>(AnotherTable::NAME, "own_name");
+        let test_against = format!(
+            r#"select "anothertable".*, "parent"."Name" as "parent_name", "anothertable"."Name" as "own_name" from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id""#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_join_fk_by() {
+        let q = Query::<AnotherTable>::select()
+            .join_fk_by_as::<TestTable>(AnotherTable::TEST_ID, "creator")
+            .join_fk_by_as::<TestTable>("Updater_Id", "updater")
+            .where_eq_as("updater", TestTable::ACTIVE, &1);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "creator" on "anothertable"."Test_Id" = "creator"."Id" join "TestDb"."TestTable" as "updater" on "anothertable"."Updater_Id" = "updater"."Id" where "updater"."Active" = :param0"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_join_by() {
+        let q = Query::<TestTable>::select()
+            .join_by::<AnotherTable>(AnotherTable::TEST_ID);
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" join "TestDb"."AnotherTable" as "anothertable" on "testtable"."Id" = "anothertable"."Test_Id""#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_join_path() {
+        let q = Query::<ThirdTable>::select()
+            .join_path::<(AnotherTable, TestTable)>()
+            .where_eq::<TestTable>(TestTable::NAME, &"Hello");
+        let test_against = format!(
+            r#"select "thirdtable".* from "TestDb"."ThirdTable" as "thirdtable" join "TestDb"."AnotherTable" as "anothertable" on "thirdtable"."Another_Id" = "anothertable"."Id" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" where "testtable"."Name" = :param0"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    fn relations() -> Relations {
+        return Relations::new()
+            .add::<AnotherTable, TestTable>()
+            .add::<ThirdTable, AnotherTable>();
+    }
+    #[test]
+    fn test_join_resolved() {
+        let q_res = Query::<ThirdTable>::select()
+            .join_resolved::<TestTable>(&relations());
+        assert!(q_res.is_ok());
+        let q = q_res.unwrap()
+            .where_eq::<TestTable>(TestTable::NAME, &"Hello");
+        let test_against = format!(
+            r#"select "thirdtable".* from "TestDb"."ThirdTable" as "thirdtable" join "TestDb"."AnotherTable" as "anothertable" on "thirdtable"."Another_Id" = "anothertable"."Id" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" where "testtable"."Name" = :param0"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+        let q_res = Query::<TestTable>::select()
+            .join_resolved::<ThirdTable>(&relations());
+        assert!(q_res.is_ok());
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" join "TestDb"."AnotherTable" as "anothertable" on "testtable"."Id" = "anothertable"."Test_Id" join "TestDb"."ThirdTable" as "thirdtable" on "anothertable"."Id" = "thirdtable"."Another_Id""#
+        );
+        assert_eq!(q_res.unwrap().query_to_string(), test_against);
+    }
+    #[test]
+    fn test_join_unresolved() {
+        let q_res = Query::<ThirdTable>::select()
+            .join_resolved::<TestTable>(&Relations::new());
+        assert!(matches!(q_res.err().unwrap(), BuildliteError::NoJoinPathError { .. }));
+        let relations = relations()
+            .add::<FourthTable, TestTable>()
+            .add::<FourthTable, ThirdTable>();
+        let q_res = Query::<AnotherTable>::select()
+            .join_resolved::<FourthTable>(&relations);
+        assert!(matches!(q_res.err().unwrap(), BuildliteError::AmbiguousJoinError { paths: 2, .. }));
+    }
+    #[test]
+    #[should_panic]
+    fn test_join_fk_from_unjoined() {
+        Query::<ThirdTable>::select()
+            .join_fk_from::<AnotherTable, TestTable>();
+    }
+    #[test]
+    fn test_where_in() {
+        let q = Query::<TestTable>::select()
+            .where_in::<TestTable>(TestTable::ID, &[ &1, &2, &3 ]).and()
+            .where_eq::<TestTable>(TestTable::ACTIVE, &1);
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Id" in (:param0, :param1, :param2) and "testtable"."Active" = :param3"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    fn active<'query>() -> impl Scope<'query, AnotherTable> {
+        return |q: Query<'query, AnotherTable>| {
+            return q.where_eq::<AnotherTable>(AnotherTable::ACTIVE, &1);
+        };
+    }
+    fn for_parent<'query>(id: &'query i64) -> impl Scope<'query, AnotherTable> {
+        return move |q: Query<'query, AnotherTable>| {
+            return q.join_fk::<TestTable>().join_and()
+                .join_fk_eq::<TestTable>(TestTable::ID, id);
+        };
+    }
+    #[test]
+    fn test_scope() {
+        let base = Query::<AnotherTable>::select()
+            .scope(active());
+        let parent = base.clone()
+            .scope(for_parent(&1));
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" where "anothertable"."Active" = :param0"#
+        );
+        assert_eq!(base.query_to_string(), test_against);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" and "testtable"."Id" = :param1 where "anothertable"."Active" = :param0"#
+        );
+        assert_eq!(parent.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_optional_filters() {
+        let name: Option<&str> = None;
+        let active = Some(1);
+        let q = Query::<TestTable>::select()
+            .where_eq_opt::<TestTable>(TestTable::NAME, name.as_ref())
+            .where_eq_opt::<TestTable>(TestTable::ACTIVE, active.as_ref())
+            .when(true, |q| q.where_gt::<TestTable>(TestTable::ID, &0))
+            .when_some(name, |q, name| q.or().where_eq::<TestTable>(TestTable::NAME, name));
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Active" = :param0 and "testtable"."Id" > :param1"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_optional_filters_or() {
+        let name: Option<&str> = None;
+        let q = Query::<TestTable>::select()
+            .where_eq_opt::<TestTable>(TestTable::NAME, name.as_ref()).or()
+            .where_eq::<TestTable>(TestTable::ID, &1).or()
+            .where_eq_opt::<TestTable>(TestTable::NAME, name.as_ref());
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Id" = :param0"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+        let q = Query::<TestTable>::select()
+            .where_eq::<TestTable>(TestTable::ID, &1).and()
+            .when(false, |q| q.where_eq::<TestTable>(TestTable::NAME, &"Hello")).or()
+            .where_eq::<TestTable>(TestTable::ID, &2);
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Id" = :param0 or "testtable"."Id" = :param1"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    #[should_panic]
+    fn test_leading_conjunction() {
+        Query::<TestTable>::select()
+            .and()
+            .where_eq::<TestTable>(TestTable::ACTIVE, &1);
+    }
+    #[test]
+    #[should_panic]
+    fn test_trailing_conjunction() {
+        Query::<TestTable>::select()
+            .where_eq::<TestTable>(TestTable::ACTIVE, &1)
+            .or()
+            .query_to_string();
+    }
+    #[test]
+    fn test_select_active() {
+        let q = Query::<AnotherTable>::select()
+            .active_only::<(AnotherTable, TestTable)>()
+            .join_fk::<TestTable>()
+            .join_fk_as::<TestTable>("parent")
+            .where_eq::<TestTable>(TestTable::ID, &1).or()
+            .where_eq::<TestTable>(TestTable::ID, &2);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id" where "anothertable"."Active" = 1 and "testtable"."Active" = 1 and "parent"."Active" = 1 and ("testtable"."Id" = :param0 or "testtable"."Id" = :param1)"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id" where "testtable"."Id" = :param0 or "testtable"."Id" = :param1"#
+        );
+        assert_eq!(q.with_inactive().query_to_string(), test_against);
+    }
+    #[test]
+    fn test_soft_delete() {
+        let q = Query::<TestTable>::soft_delete()
+            .where_eq::<TestTable>(TestTable::ID, &1);
+        let test_against = format!(
+            r#"update "TestDb"."TestTable" set "Active" = :param0 where "Id" = :param1"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_orderby_join() {
+        let q = Query::<AnotherTable>::select()
+            .join_fk::<TestTable>()
+            .orderby_asc_on::<TestTable>(TestTable::NAME).nulls_last()
+            .orderby_expr_desc_unchecked("length(anothertable.Name)").collate("nocase")
+            .limit(10);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "testtable" on "anothertable"."Test_Id" = "testtable"."Id" order by "testtable"."Name" asc nulls last, length(anothertable.Name) collate "nocase" desc limit 10"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_update_orderby_limit() {
+        let q = Query::<TestTable>::update()
+            .set(TestTable::ACTIVE, &0)
+            .orderby_desc(TestTable::ID)
+            .limit(1);
+        let test_against = format!(
+            r#"update "TestDb"."TestTable" set "Active" = :param0 where "Id" in (select "Id" from "TestDb"."TestTable" order by "Id" desc limit 1)"#
+        );
+        assert_eq!(q.query_to_string(), test_against);
+    }
+    #[test]
+    fn test_order_modifiers_follow_term() {
+        let q = Query::<AnotherTable>::select()
+            .orderby_asc(AnotherTable::NAME).collate("nocase").nulls_first()
+            .orderby_desc(AnotherTable::ID).nulls_last();
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" order by "anothertable"."Name" collate "nocase" asc nulls first, "anothertable"."Id" desc nulls last"#
         );
         assert_eq!(q.query_to_string(), test_against);
     }
+    #[test]
+    #[should_panic]
+    fn test_order_modifiers_without_orderby() {
+        Query::<AnotherTable>::select()
+            .nulls_first()
+            .orderby_asc(AnotherTable::NAME);
+    }
+    #[test]
+    #[should_panic]
+    fn test_update_orderby_without_limit() {
+        Query::<TestTable>::update()
+            .set(TestTable::ACTIVE, &0)
+            .orderby_desc(TestTable::ID)
+            .query_to_string();
+    }
+    #[test]
+    fn test_debug_sql() {
+        let q = Query::<TestTable>::select()
+            .where_eq::<TestTable>(TestTable::NAME, &"O'Brien").and()
+            .where_in::<TestTable>(TestTable::ID, &[ &1, &2 ]).and()
+            .where_gt::<TestTable>(TestTable::ACTIVE, &0.5).or()
+            .where_eq::<TestTable>(TestTable::NAME, Param::owned(vec![ 0u8, 171, 255 ])).or()
+            .where_eq::<TestTable>(TestTable::NAME, Param::owned(Option::<String>::None));
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Name" = 'O''Brien' and "testtable"."Id" in (1, 2) and "testtable"."Active" > 0.5 or "testtable"."Name" = x'00abff' or "testtable"."Name" = null"#
+        );
+        assert_eq!(q.to_debug_sql(), test_against);
+        let test_against = format!(
+            r#"select "testtable".* from "TestDb"."TestTable" as "testtable" where "testtable"."Name" = [redacted] and "testtable"."Id" in (1, 2) and "testtable"."Active" > 0.5 or "testtable"."Name" = [redacted] or "testtable"."Name" = [redacted]"#
+        );
+        assert_eq!(q.sensitive(TestTable::NAME).to_debug_sql(), test_against);
+    }
+    #[test]
+    fn test_sensitive_alias() {
+        let q = Query::<AnotherTable>::select()
+            .join_fk_as::<TestTable>("parent")
+            .where_eq::<AnotherTable>(AnotherTable::NAME, &"own").and()
+            .where_eq_as("parent", TestTable::NAME, &"secret");
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id" where "anothertable"."Name" = 'own' and "parent"."Name" = [redacted]"#
+        );
+        assert_eq!(q.clone().sensitive_as("parent", TestTable::NAME).to_debug_sql(), test_against);
+        let test_against = format!(
+            r#"select "anothertable".* from "TestDb"."AnotherTable" as "anothertable" join "TestDb"."TestTable" as "parent" on "anothertable"."Test_Id" = "parent"."Id" where "anothertable"."Name" = [redacted] and "parent"."Name" = 'secret'"#
+        );
+        assert_eq!(q.sensitive(AnotherTable::NAME).to_debug_sql(), test_against);
+    }
+    #[test]
+    fn test_sensitive_registered() {
+        register_sensitive_columns::<SecretTable>();
+        let q = Query::<SecretTable>::select()
+            .join_fk::<TestTable>()
+            .where_eq::<TestTable>(TestTable::NAME, &"own").and()
+            .where_eq::<SecretTable>(SecretTable::TOKEN, &"secret");
+        let test_against = format!(
+            r#"select "secrettable".* from "TestDb"."SecretTable" as "secrettable" join "TestDb"."TestTable" as "testtable" on "secrettable"."Test_Id" = "testtable"."Id" where "testtable"."Name" = 'own' and "secrettable"."Token" = [redacted]"#
+        );
+        assert_eq!(q.to_debug_sql(), test_against);
+    }
 }
 mod execution {
     use {
-        crate::Query,
+        crate::{
+            BuildliteError,
+            CancelToken,
+            Param,
+            Query,
+            QueryEvent,
+            QueryObserver,
+            SensitiveColumns,
+            in_transaction,
+        },
         migaton::traits::{
             DoMigrations,
             Migrations,
         },
         serial_test::serial,
+        std::{
+            cell::Cell,
+            panic::{
+                catch_unwind,
+                AssertUnwindSafe,
+            },
+            sync::{
+                Arc,
+                Mutex,
+            },
+            time::Duration,
+        },
         worm::{
             core::{
+                sql::{
+                    Result as RusqliteResult,
+                    ToSql,
+                    types::ToSqlOutput,
+                },
                 DbContext,
                 DbCtx,
+                DbModel,
                 ForeignKey,
                 PrimaryKey,
             },
@@ -111,6 +478,17 @@ mod execution {
         #[dbcolumn(column(name="Name", insertable))]
         name: String,
     }
+    impl SensitiveColumns for Item {
+        const SENSITIVE: &'static [&'static str] = &[ Item::NAME ];
+    }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="BuildliteDb", name="Item", alias="nameditem"))]
+    struct NamedItem {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Name", unique_name, insertable))]
+        name: String,
+    }
     #[derive(Worm)]
     #[dbmodel(table(schema="BuildliteDb", name="Secondary", alias="secondary"))]
     struct Secondary {
@@ -121,6 +499,24 @@ mod execution {
         #[dbcolumn(column(name="Name", insertable))]
         name: String,
     }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="BuildliteDb", name="Tag", alias="tag"))]
+    struct Tag {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Name", insertable))]
+        name: String,
+    }
+    #[derive(Worm)]
+    #[dbmodel(table(schema="BuildliteDb", name="ItemTag", alias="itemtag"))]
+    struct ItemTag {
+        #[dbcolumn(column(name="Id", primary_key))]
+        id: i64,
+        #[dbcolumn(column(name="Item_Id", foreign_key="Item", insertable))]
+        item_id: i64,
+        #[dbcolumn(column(name="Tag_Id", foreign_key="Tag", insertable))]
+        tag_id: i64,
+    }
     fn get_db_ctx() -> (Database, Database) {
         let mut mem_db = Database::init();
         mem_db.context.attach_temp_dbs();
@@ -261,4 +657,718 @@ mod execution {
         join_on_item(&mut db, &p, &s);
         migrate_down(&mut mem_db, &mut db);
     }
+    #[test]
+    #[serial]
+    fn select_aliased_columns() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        new_secondary(&mut db, &p);
+        let q_res = Query::<Secondary>::select()
+            .join_fk_as::<Item>("parent")
+            .select_as("parent", Item::NAME, "parent_name")
+            .execute_map(&mut db, |row| {
+                let s = Secondary::from_row(row)?;
+                let name: String = row.get("parent_name")?;
+                return Ok((s, name));
+            });
+        assert!(q_res.is_ok());
+        let q = q_res.unwrap();
+        assert_eq!(q.len(), 1);
+        assert_eq!(q[0].0.get_name(), SECONDARY_NAME);
+        assert_eq!(q[0].1, PRIMARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn eager_load_secondary() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let s = new_secondary(&mut db, &p);
+        let q_res = Query::<Item>::select()
+            .execute_with::<Secondary, _>(&mut db, |q| q);
+        assert!(q_res.is_ok());
+        let q = q_res.unwrap();
+        assert_eq!(q.len(), 1);
+        assert_eq!(q[0].0.get_id(), p.get_id());
+        assert_eq!(q[0].1.len(), 1);
+        assert_eq!(q[0].1[0].get_id(), s.get_id());
+        let f_res = Query::<Item>::select()
+            .execute_with::<Secondary, _>(&mut db, |q| {
+                return q.where_ne::<Secondary>(Secondary::NAME, &SECONDARY_NAME).or()
+                    .where_eq::<Secondary>(Secondary::ID, &0);
+            });
+        assert!(f_res.is_ok());
+        let f = f_res.unwrap();
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].1.len(), 0);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn select_one_item() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let o_res = Query::<Item>::select()
+            .where_eq::<Item>(Item::NAME, &SECONDARY_NAME)
+            .execute_optional(&mut db);
+        assert!(o_res.is_ok());
+        assert!(o_res.unwrap().is_none());
+        let q_res = Query::<Item>::select()
+            .execute_one(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_id(), p.get_id());
+        assert!(Item::insert_new(&mut db, SECONDARY_NAME.to_string()).is_ok());
+        let m_res = Query::<Item>::select()
+            .execute_one(&mut db);
+        assert!(matches!(m_res, Err(BuildliteError::TooManyRowsError)));
+        let f_res = Query::<Item>::select()
+            .orderby_desc(Item::ID)
+            .execute_optional(&mut db);
+        assert!(f_res.is_ok());
+        assert_eq!(f_res.unwrap().unwrap().get_name(), SECONDARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn count_secondary() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        new_secondary(&mut db, &p);
+        let c_res = Query::<Secondary>::select()
+            .join_fk::<Item>()
+            .where_eq::<Item>(Item::NAME, &PRIMARY_NAME)
+            .execute_count(&mut db);
+        assert!(c_res.is_ok());
+        assert_eq!(c_res.unwrap(), 1);
+        let e_res = Query::<Secondary>::select()
+            .where_eq::<Secondary>(Secondary::NAME, &PRIMARY_NAME)
+            .execute_exists(&mut db);
+        assert!(e_res.is_ok());
+        assert!(!e_res.unwrap());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn compiled_select() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let mut c = Query::<Item>::select()
+            .where_eq::<Item>(Item::NAME, &SECONDARY_NAME)
+            .compile();
+        assert_eq!(c.param_count(), 1);
+        let n_res = c.execute(&mut db);
+        assert!(n_res.is_ok());
+        assert_eq!(n_res.unwrap().len(), 0);
+        c.bind(0, &PRIMARY_NAME);
+        let q_res = c.execute_row(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_id(), p.get_id());
+        new_secondary(&mut db, &p);
+        let mut j = Query::<Item>::select()
+            .where_eq::<Item>(Item::NAME, &SECONDARY_NAME)
+            .join::<Secondary>().join_and()
+            .join_eq::<Secondary>(Secondary::NAME, &SECONDARY_NAME)
+            .compile();
+        assert_eq!(j.param_count(), 2);
+        j.bind(0, &PRIMARY_NAME);
+        let j_res = j.execute(&mut db);
+        assert!(j_res.is_ok());
+        assert_eq!(j_res.unwrap().len(), 1);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    fn item_by_name(name: &str) -> Query<'static, Item> {
+        return Query::<Item>::select()
+            .where_eq::<Item>(Item::NAME, Param::owned(name.to_string()));
+    }
+    #[test]
+    #[serial]
+    fn owned_params() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let q = item_by_name(PRIMARY_NAME);
+        let handle = std::thread::spawn(move || q);
+        let q_res = handle.join().unwrap().execute_row(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_id(), p.get_id());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    struct CellId(Cell<i64>);
+    impl ToSql for CellId {
+        fn to_sql(&self) -> RusqliteResult<ToSqlOutput<'_>> {
+            return Ok(ToSqlOutput::from(self.0.get()));
+        }
+    }
+    #[test]
+    #[serial]
+    fn borrowed_params() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let id = CellId(Cell::new(p.get_id()));
+        let name: &dyn ToSql = &PRIMARY_NAME;
+        let q_res = Query::<Item>::select()
+            .where_eq::<Item>(Item::ID, &id).and()
+            .where_eq::<Item>(Item::NAME, name)
+            .execute_row(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_id(), p.get_id());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn find_items() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let k_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(k_res.is_ok());
+        assert_eq!(k_res.unwrap().get_name(), PRIMARY_NAME);
+        let n_res = Query::<NamedItem>::find_by_unique_name(&mut db, PRIMARY_NAME);
+        assert!(n_res.is_ok());
+        assert_eq!(n_res.unwrap().get_id(), p.get_id());
+        let m_res = Query::<Item>::find_many_by_pk(&mut db, &[ p.get_id(), p.get_id() + 1 ]);
+        assert!(m_res.is_ok());
+        assert_eq!(m_res.unwrap().len(), 1);
+        let ids = (0..1200).map(|i| p.get_id() + i).collect::<Vec<i64>>();
+        let m_res = Query::<Item>::find_many_by_pk(&mut db, &ids);
+        assert!(m_res.is_ok());
+        assert_eq!(m_res.unwrap().len(), 1);
+        let e_res = Query::<Item>::find_by_pk(&mut db, p.get_id() + 1);
+        assert!(matches!(e_res, Err(BuildliteError::NoRowsError)));
+        migrate_down(&mut mem_db, &mut db);
+    }
+    fn rename_item(db: &mut impl DbCtx, p: &Item, name: &str) {
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, Param::owned(name.to_string()))
+            .where_eq::<Item>(Item::ID, Param::owned(p.get_id()))
+            .execute_update(db);
+        assert!(u_res.is_ok());
+        assert_eq!(u_res.unwrap(), 1);
+    }
+    #[test]
+    #[serial]
+    fn transaction_rollback() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let t_res: Result<(), BuildliteError> = in_transaction(&mut db, |tx| {
+            rename_item(tx, &p, SECONDARY_NAME);
+            let s_res: Result<(), BuildliteError> = tx.savepoint(|sp| {
+                rename_item(sp, &p, "Inner");
+                return Err(BuildliteError::NoRowsError);
+            });
+            assert!(s_res.is_err());
+            let q = Query::<Item>::find_by_pk(tx, p.get_id())?;
+            assert_eq!(q.get_name(), SECONDARY_NAME);
+            return Err(BuildliteError::NoRowsError);
+        });
+        assert!(t_res.is_err());
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), PRIMARY_NAME);
+        let c_res: Result<(), BuildliteError> = in_transaction(&mut db, |tx| {
+            rename_item(tx, &p, SECONDARY_NAME);
+            return Ok(());
+        });
+        assert!(c_res.is_ok());
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), SECONDARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn transaction_panic() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let t_res = catch_unwind(AssertUnwindSafe(|| {
+            let _: Result<(), BuildliteError> = in_transaction(&mut db, |tx| {
+                rename_item(tx, &p, SECONDARY_NAME);
+                panic!("Cannot continue");
+            });
+        }));
+        assert!(t_res.is_err());
+        assert!(db.use_connection().is_autocommit());
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), PRIMARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn nested_transactions() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let t_res: Result<(), BuildliteError> = in_transaction(&mut db, |tx| {
+            rename_item(tx, &p, SECONDARY_NAME);
+            let n_res: Result<(), BuildliteError> = in_transaction(tx, |inner| {
+                rename_item(inner, &p, "Inner");
+                return Err(BuildliteError::NoRowsError);
+            });
+            assert!(n_res.is_err());
+            assert!(!tx.use_connection().is_autocommit());
+            return Ok(());
+        });
+        assert!(t_res.is_ok());
+        assert!(db.use_connection().is_autocommit());
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), SECONDARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn guarded_updates() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .execute_update(&mut db);
+        assert!(u_res.is_err());
+        assert!(matches!(u_res.unwrap_err(), BuildliteError::UnfilteredUpdateError));
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .all_rows()
+            .expect_rows(2)
+            .execute_update(&mut db);
+        assert!(u_res.is_err());
+        assert!(matches!(
+            u_res.unwrap_err(),
+            BuildliteError::UnexpectedRowsError { expected: 2, actual: 1 }
+        ));
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), PRIMARY_NAME);
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .expect_rows(1)
+            .execute_update(&mut db);
+        assert!(u_res.is_ok());
+        assert_eq!(u_res.unwrap(), 1);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn update_orderby_limit() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let s_res = Item::insert_new(&mut db, SECONDARY_NAME.to_string());
+        assert!(s_res.is_ok());
+        let s = s_res.unwrap();
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &"Newest")
+            .where_gt::<Item>(Item::ID, &0)
+            .orderby_desc(Item::ID)
+            .limit(1)
+            .execute_update(&mut db);
+        assert!(u_res.is_ok());
+        assert_eq!(u_res.unwrap(), 1);
+        let q_res = Query::<Item>::find_by_pk(&mut db, s.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), "Newest");
+        let q_res = Query::<Item>::find_by_pk(&mut db, p.get_id());
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_name(), PRIMARY_NAME);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn unknown_columns() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        new_item(&mut db);
+        let sort_by = "Name; drop table Item; --";
+        let q_res = Query::<Item>::select()
+            .orderby_asc(sort_by)
+            .execute(&mut db);
+        assert!(q_res.is_err());
+        assert!(matches!(q_res.err().unwrap(), BuildliteError::UnknownColumnError { .. }));
+        let q_res = Query::<Item>::select()
+            .orderby_asc("name")
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().len(), 1);
+        let q_res = Query::<Item>::select()
+            .where_eq_as("ghost", Item::ID, &1)
+            .execute(&mut db);
+        assert!(q_res.is_err());
+        assert!(matches!(q_res.err().unwrap(), BuildliteError::UnknownAliasError { .. }));
+        let c = Query::<Item>::select()
+            .orderby_asc(sort_by)
+            .compile();
+        for _ in 0..2 {
+            let c_res = c.execute(&mut db);
+            assert!(c_res.is_err());
+            assert!(matches!(c_res.err().unwrap(), BuildliteError::UnknownColumnError { .. }));
+        }
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn unique_violation() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let i_res = Item::insert_new(&mut db, SECONDARY_NAME.to_string());
+        assert!(i_res.is_ok());
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .execute_update(&mut db);
+        assert!(u_res.is_err());
+        let e = u_res.unwrap_err();
+        assert!(matches!(e, BuildliteError::UniqueError { .. }));
+        assert!(e.sql().is_some());
+        assert_eq!(e.param_count(), Some(2));
+        assert!(std::error::Error::source(&e).is_some());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn sensitive_params() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let i_res = Item::insert_new(&mut db, SECONDARY_NAME.to_string());
+        assert!(i_res.is_ok());
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .sensitive_columns::<Item>()
+            .execute_update(&mut db);
+        assert!(u_res.is_err());
+        let e = u_res.unwrap_err();
+        assert_eq!(e.debug_params().unwrap(), &[ "[redacted]".to_string(), p.get_id().to_string() ]);
+        assert!(!e.to_string().contains(SECONDARY_NAME));
+        assert!(e.to_string().contains("with 2 parameters"));
+        migrate_down(&mut mem_db, &mut db);
+    }
+    const SLOW_EXPR: &'static str = "(with recursive c(x) as (select 1 union all select x + 1 from c limit 1000000000) select count(*) from c)";
+    #[test]
+    #[serial]
+    fn query_timeout() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        new_item(&mut db);
+        let q_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked(SLOW_EXPR)
+            .timeout(Duration::from_millis(50))
+            .execute(&mut db);
+        assert!(q_res.is_err());
+        assert!(matches!(q_res.err().unwrap(), BuildliteError::TimeoutError(_)));
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let c_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked(SLOW_EXPR)
+            .cancel_token(&token)
+            .execute(&mut db);
+        handle.join().unwrap();
+        assert!(c_res.is_err());
+        assert!(matches!(c_res.err().unwrap(), BuildliteError::CancelledError));
+        let q_res = Query::<Item>::select()
+            .timeout(Duration::from_secs(1))
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().len(), 1);
+        let q_res = Query::<Item>::select()
+            .timeout(Duration::from_millis(10))
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        std::thread::sleep(Duration::from_millis(20));
+        let q_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked("(with recursive c(x) as (select 1 union all select x + 1 from c limit 100000) select count(*) from c)")
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn row_iter_timeout() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        new_item(&mut db);
+        let r_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked(SLOW_EXPR)
+            .timeout(Duration::from_millis(50))
+            .for_each_row(&mut db, |_| true);
+        assert!(r_res.is_err());
+        assert!(matches!(r_res.err().unwrap(), BuildliteError::TimeoutError(_)));
+        let p_res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = Query::<Item>::select()
+                .timeout(Duration::from_millis(10))
+                .execute_iter(&mut db, |_| -> usize { panic!("abort iteration") });
+        }));
+        assert!(p_res.is_err());
+        std::thread::sleep(Duration::from_millis(20));
+        let q_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked("(with recursive c(x) as (select 1 union all select x + 1 from c limit 100000) select count(*) from c)")
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn observe_queries() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = events.clone();
+        let observer: Arc<dyn QueryObserver> = Arc::new(move |event: &QueryEvent| {
+            recorder.lock().unwrap().push((event.sql.to_string(), event.params.clone(), event.rows));
+        });
+        let q_res = Query::<Item>::select()
+            .where_eq::<Item>(Item::NAME, &PRIMARY_NAME)
+            .observe(observer.clone())
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        let u_res = Query::<Item>::update()
+            .set(Item::NAME, &SECONDARY_NAME)
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .observe(observer.clone())
+            .redact_params()
+            .execute_update(&mut db);
+        assert!(u_res.is_ok());
+        let t_res = Query::<Item>::select()
+            .observe(observer.clone())
+            .execute_through::<ItemTag, Tag>(&mut db);
+        assert!(t_res.is_ok());
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].0, r#"select "item".* from "BuildliteDb"."Item" as "item" where "item"."Name" = ?"#);
+        assert_eq!(events[0].1, vec![ format!("'{}'", PRIMARY_NAME) ]);
+        assert_eq!(events[0].2, Some(1));
+        assert_eq!(events[1].1, vec![ "[redacted]".to_string(), "[redacted]".to_string() ]);
+        assert_eq!(events[1].2, Some(1));
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn explain_plans() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        new_secondary(&mut db, &p);
+        let p_res = Query::<Item>::select()
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .explain(&mut db);
+        assert!(p_res.is_ok());
+        let plan = p_res.unwrap();
+        assert_eq!(plan.nodes.len(), 1);
+        assert!(plan.nodes[0].uses_index());
+        assert!(plan.full_scans().is_empty());
+        let q_res = Query::<Item>::select()
+            .where_eq::<Item>(Item::ID, &p.get_id())
+            .scan_guard(0)
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        let s_res = Query::<Secondary>::select()
+            .where_eq::<Secondary>(Secondary::ITEM_ID, &p.get_id())
+            .scan_guard(0)
+            .execute(&mut db);
+        assert!(s_res.is_err());
+        assert!(matches!(s_res.err().unwrap(), BuildliteError::FullScanError { .. }));
+        let s_res = Query::<Secondary>::select()
+            .where_eq::<Secondary>(Secondary::ITEM_ID, &p.get_id())
+            .scan_guard(1)
+            .execute(&mut db);
+        assert!(s_res.is_ok());
+        let c = Query::<Secondary>::select()
+            .where_eq::<Secondary>(Secondary::ITEM_ID, &p.get_id())
+            .scan_guard(0)
+            .compile();
+        let c_res = c.execute(&mut db);
+        assert!(c_res.is_err());
+        assert!(matches!(c_res.err().unwrap(), BuildliteError::FullScanError { .. }));
+        let q_res = Query::<Item>::select()
+            .orderby_expr_asc_unchecked("(with recursive c(x) as (select 1 union all select x + 1 from c where x < item.Id) select count(*) from c)")
+            .scan_guard(1)
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn iterate_items() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let i_res = Query::<Item>::select()
+            .execute_iter(&mut db, |rows| {
+                return rows.map(|row| row.unwrap().get_id()).collect::<Vec<i64>>();
+            });
+        assert!(i_res.is_ok());
+        assert_eq!(i_res.unwrap(), vec![ p.get_id() ]);
+        let mut count = 0;
+        let f_res = Query::<Item>::select()
+            .for_each_row(&mut db, |_| {
+                count += 1;
+                return false;
+            });
+        assert!(f_res.is_ok());
+        assert_eq!(count, 1);
+        migrate_down(&mut mem_db, &mut db);
+    }
+    const TAG_NAMES: [&'static str; 2] = [ "Red", "Blue" ];
+    fn new_tags(db: &mut Database, p: &Item) -> Vec<Tag> {
+        let mut tags = Vec::new();
+        for name in TAG_NAMES {
+            let t_res = Tag::insert_new(db, name.to_string());
+            assert!(t_res.is_ok());
+            let t = t_res.unwrap();
+            let it_res = ItemTag::insert_new(db, p.get_id(), t.get_id());
+            assert!(it_res.is_ok());
+            tags.push(t);
+        }
+        return tags;
+    }
+    #[test]
+    #[serial]
+    fn join_through_tags() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        new_tags(&mut db, &p);
+        let q_res = Query::<Item>::select()
+            .join_through::<ItemTag, Tag>()
+            .where_eq::<Tag>(Tag::NAME, &TAG_NAMES[1])
+            .execute_row(&mut db);
+        assert!(q_res.is_ok());
+        assert_eq!(q_res.unwrap().get_id(), p.get_id());
+        let l_res = Query::<Item>::select()
+            .execute_through::<ItemTag, Tag>(&mut db);
+        assert!(l_res.is_ok());
+        let l = l_res.unwrap();
+        assert_eq!(l.len(), 1);
+        assert_eq!(l[0].0.get_id(), p.get_id());
+        assert_eq!(l[0].1.len(), TAG_NAMES.len());
+        migrate_down(&mut mem_db, &mut db);
+    }
+    #[test]
+    #[serial]
+    fn bind_many_filters() {
+        let (mut mem_db, mut db) = get_db_ctx();
+        migrate_up(&mut mem_db, &mut db);
+        let p = new_item(&mut db);
+        let s = new_secondary(&mut db, &p);
+        let tags = new_tags(&mut db, &p);
+        let t = &tags[1];
+        let ids = (0..3).map(|i| Param::owned(p.get_id() + i * 10));
+        let q_res = Query::<Item>::select()
+            .join::<Secondary>()
+            .join_through::<ItemTag, Tag>()
+            .where_eq::<Item>(Item::ID, &p.get_id()).and()
+            .where_eq::<Item>(Item::NAME, &PRIMARY_NAME).and()
+            .where_eq::<Secondary>(Secondary::ID, &s.get_id()).and()
+            .where_eq::<Secondary>(Secondary::ITEM_ID, &p.get_id()).and()
+            .where_eq::<Secondary>(Secondary::NAME, &SECONDARY_NAME).and()
+            .where_ne::<Secondary>(Secondary::NAME, &"Missing").and()
+            .where_gt::<ItemTag>(ItemTag::ID, &1).and()
+            .where_eq::<ItemTag>(ItemTag::ITEM_ID, &p.get_id()).and()
+            .where_eq::<ItemTag>(ItemTag::TAG_ID, &t.get_id()).and()
+            .where_eq::<Tag>(Tag::ID, &t.get_id()).and()
+            .where_eq::<Tag>(Tag::NAME, &TAG_NAMES[1]).and()
+            .where_in::<Item>(Item::ID, ids)
+            .execute(&mut db);
+        assert!(q_res.is_ok());
+        let q = q_res.unwrap();
+        assert_eq!(q.len(), 1);
+        assert_eq!(q[0].get_id(), p.get_id());
+        migrate_down(&mut mem_db, &mut db);
+    }
+}
+mod retry {
+    use {
+        crate::{
+            BuildliteError,
+            CancelToken,
+            RetryPolicy,
+            interrupt::Interrupt,
+        },
+        std::time::{
+            Duration,
+            Instant,
+        },
+        worm::core::sql::{
+            Error as RusqliteError,
+            ffi,
+        },
+    };
+    fn busy() -> BuildliteError {
+        return BuildliteError::from(
+            RusqliteError::SqliteFailure(ffi::Error::new(ffi::SQLITE_BUSY), None)
+        );
+    }
+    #[test]
+    fn retry_exhausted() {
+        let mut attempts = 0;
+        let policy = RetryPolicy::new(3).backoff(Duration::from_millis(1));
+        let res: Result<(), BuildliteError> = policy.run(|| {
+            attempts += 1;
+            return Err(busy());
+        });
+        assert_eq!(attempts, 3);
+        assert!(matches!(res, Err(BuildliteError::BusyError(..))));
+    }
+    #[test]
+    fn retry_recovers() {
+        let mut attempts = 0;
+        let policy = RetryPolicy::new(3).backoff(Duration::from_millis(1));
+        let res = policy.run(|| {
+            attempts += 1;
+            if attempts < 2 {
+                return Err(busy());
+            }
+            return Ok(attempts);
+        });
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), 2);
+    }
+    #[test]
+    fn retry_skips_other_errors() {
+        let mut attempts = 0;
+        let policy = RetryPolicy::new(3).backoff(Duration::from_millis(1));
+        let res: Result<(), BuildliteError> = policy.run(|| {
+            attempts += 1;
+            return Err(BuildliteError::NoRowsError);
+        });
+        assert_eq!(attempts, 1);
+        assert!(matches!(res, Err(BuildliteError::NoRowsError)));
+    }    #[test]
+    fn retry_stops_at_deadline() {
+        let interrupt = Interrupt { timeout: Some(Duration::from_millis(50)), cancel: None };
+        let watch = interrupt.watch();
+        let policy = RetryPolicy::new(100).backoff(Duration::from_millis(20));
+        let start = Instant::now();
+        let res: Result<(), BuildliteError> = policy.run_watched(&watch, || {
+            return Err(busy());
+        });
+        assert!(matches!(res, Err(BuildliteError::TimeoutError(_))));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        let token = CancelToken::new();
+        token.cancel();
+        let interrupt = Interrupt { timeout: None, cancel: Some(token) };
+        let mut attempts = 0;
+        let res: Result<(), BuildliteError> = policy.run_watched(&interrupt.watch(), || {
+            attempts += 1;
+            return Err(busy());
+        });
+        assert_eq!(attempts, 0);
+        assert!(matches!(res, Err(BuildliteError::CancelledError)));
+    }
 }
@@ -0,0 +1,108 @@
+use {
+    crate::error::{
+        BuildliteError,
+        BuildliteErrorMatch,
+    },
+    worm::core::{
+        DbCtx,
+        sql::Connection,
+    },
+};
+pub struct Transaction<'tx, D> where D: DbCtx {
+    db: &'tx mut D,
+    depth: usize,
+    rollback: String,
+    open: bool,
+}
+impl<'tx, D> DbCtx for Transaction<'tx, D> where D: DbCtx {
+    fn use_connection(&mut self) -> &mut Connection {
+        return self.db.use_connection();
+    }
+}
+impl<'tx, D> Drop for Transaction<'tx, D> where D: DbCtx {
+    fn drop(&mut self) {
+        // also reached when the closure panics, so nothing is left open on the connection
+        if self.open {
+            let _ = self.db.use_connection().execute_batch(&self.rollback);
+        }
+    }
+}
+fn run<'a, D, F, R, E>(
+    db: &mut D,
+    depth: usize,
+    begin: &'a str,
+    commit: &'a str,
+    rollback: &'a str,
+    f: F
+) -> Result<R, E>
+where
+    D: DbCtx,
+    F: FnOnce(&mut Transaction<D>) -> Result<R, E>,
+    E: From<BuildliteError>,
+{
+    db.use_connection().execute_batch(begin).quick_match()?;
+    let mut tx = Transaction {
+        db,
+        depth,
+        rollback: rollback.to_string(),
+        open: true,
+    };
+    let val = f(&mut tx)?;
+    tx.use_connection().execute_batch(commit).quick_match()?;
+    tx.open = false;
+    return Ok(val);
+}
+pub fn in_transaction<D, F, R, E>(db: &mut D, f: F) -> Result<R, E>
+where
+    D: DbCtx,
+    F: FnOnce(&mut Transaction<D>) -> Result<R, E>,
+    E: From<BuildliteError>,
+{
+    if !db.use_connection().is_autocommit() {
+        // already inside a transaction, so nest as a savepoint instead of failing on begin
+        return run(
+            db,
+            0,
+            "savepoint buildlite_tx",
+            "release buildlite_tx",
+            "rollback to buildlite_tx; release buildlite_tx",
+            f,
+        );
+    }
+    return run(db, 0, "begin", "commit", "rollback", f);
+}
+pub(crate) fn in_savepoint<'a, D, F, R>(db: &mut D, name: &'a str, f: F) -> Result<R, BuildliteError>
+where
+    D: DbCtx,
+    F: FnOnce(&mut Transaction<D>) -> Result<R, BuildliteError>,
+{
+    return run(
+        db,
+        0,
+        &format!("savepoint {}", name),
+        &format!("release {}", name),
+        &format!("rollback to {}; release {}", name, name),
+        f,
+    );
+}
+impl<'tx, D> Transaction<'tx, D> where D: DbCtx {
+    pub fn depth(&self) -> usize {
+        return self.depth;
+    }
+    pub fn savepoint<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut Transaction<D>) -> Result<R, E>,
+        E: From<BuildliteError>,
+    {
+        let depth = self.depth + 1;
+        let name = format!("buildlite_sp{}", depth);
+        return run(
+            self.db,
+            depth,
+            &format!("savepoint {}", name),
+            &format!("release {}", name),
+            &format!("rollback to {}; release {}", name, name),
+            f,
+        );
+    }
+}
@@ -0,0 +1,4 @@
+select count(*)
+from BuildliteDb.sqlite_master
+where Name = 'ItemTag'
+and Type = 'table';
@@ -0,0 +1,8 @@
+create table BuildliteDb.ItemTag
+	(
+		Id integer primary key autoincrement
+	,	Item_Id integer not null
+	,	Tag_Id integer not null
+	,	foreign key (Item_Id) references Item (Id)
+	,	foreign key (Tag_Id) references Tag (Id)
+	);
@@ -0,0 +1,5 @@
+create table BuildliteDb.Tag
+	(
+		Id integer primary key autoincrement
+	,	Name text unique not null
+	);